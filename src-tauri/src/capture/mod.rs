@@ -0,0 +1,230 @@
+//! Turns captured screenshots into what the capture commands return.
+//!
+//! Every capture command accepts [`CaptureOptions`] to pick the image format
+//! and shrink the image, and answers with a [`CaptureOutput`] reporting both
+//! the captured and the encoded size. The encoded bytes never cross IPC as
+//! JSON: they are kept in a small store and served by the `quack-capture://`
+//! protocol (see [`serve`]), so the frontend can use [`CaptureOutput::url`] as
+//! an `<img src>` or `fetch` it to upload the bytes as they are.
+//!
+//! Before encoding, sensitive areas can be blacked out (see [`redact`]).
+
+use crate::error::QuackError;
+use crate::platform::native;
+use image::{imageops::FilterType, DynamicImage, ImageOutputFormat, RgbaImage};
+use redact::{CaptureArea, Redaction, RedactionOptions};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    io,
+    sync::{Mutex, OnceLock},
+};
+use tauri::http::{header, Request, Response, StatusCode};
+
+pub mod redact;
+
+/// Quality used for JPEG when none is given.
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+/// Number of encoded captures kept for `quack-capture://`.
+const STORE_CAPACITY: usize = 8;
+/// Name of the URI scheme captures are served under.
+pub const URI_SCHEME: &str = "quack-capture";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// Lossless WebP.
+    Webp,
+}
+
+impl OutputFormat {
+    fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "PNG",
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Webp => "WebP",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct CaptureOptions {
+    pub format: OutputFormat,
+    /// Downscale so the longer edge is at most this many pixels.
+    pub max_edge: Option<u32>,
+    /// JPEG quality from 1 to 100. Ignored by the lossless formats.
+    pub quality: u8,
+    pub grayscale: bool,
+    /// Black out sensitive areas before encoding.
+    pub redact: Option<RedactionOptions>,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            format: OutputFormat::Png,
+            max_edge: None,
+            quality: DEFAULT_JPEG_QUALITY,
+            grayscale: false,
+            redact: None,
+        }
+    }
+}
+
+/// An encoded screenshot.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CaptureOutput {
+    pub id: u64,
+    /// Where the encoded image is served while it stays in the store.
+    pub url: String,
+    pub mime_type: &'static str,
+    /// Size of the image as captured, in pixels.
+    pub original_width: u32,
+    pub original_height: u32,
+    /// Size of the encoded image after downscaling, in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Length of the encoded image in bytes.
+    pub byte_len: usize,
+    /// What was blacked out, empty unless redaction was requested.
+    pub redactions: Vec<Redaction>,
+}
+
+struct StoredCapture {
+    id: u64,
+    mime_type: &'static str,
+    bytes: Vec<u8>,
+}
+
+/// The most recent encoded captures, oldest first.
+pub struct CaptureStore {
+    captures: VecDeque<StoredCapture>,
+    next_id: u64,
+}
+
+impl CaptureStore {
+    fn insert(&mut self, mime_type: &'static str, bytes: Vec<u8>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.captures.push_back(StoredCapture {
+            id,
+            mime_type,
+            bytes,
+        });
+        while self.captures.len() > STORE_CAPACITY {
+            self.captures.pop_front();
+        }
+        id
+    }
+
+    fn get(&self, id: u64) -> Option<&StoredCapture> {
+        self.captures.iter().find(|c| c.id == id)
+    }
+}
+
+/// The process-wide capture store.
+pub fn store() -> &'static Mutex<CaptureStore> {
+    static STORE: OnceLock<Mutex<CaptureStore>> = OnceLock::new();
+    STORE.get_or_init(|| {
+        Mutex::new(CaptureStore {
+            captures: VecDeque::new(),
+            next_id: 1,
+        })
+    })
+}
+
+/// The URL the webview loads capture `id` from. WebView2 only serves custom
+/// schemes over `http://<scheme>.localhost`.
+fn capture_url(id: u64) -> String {
+    if cfg!(target_os = "windows") {
+        format!("http://{}.localhost/{}", URI_SCHEME, id)
+    } else {
+        format!("{}://localhost/{}", URI_SCHEME, id)
+    }
+}
+
+/// Handles `quack-capture://localhost/<id>` requests from the webview.
+pub fn serve<R: tauri::Runtime>(
+    _ctx: tauri::UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+) -> Response<Cow<'static, [u8]>> {
+    let id = request.uri().path().trim_start_matches('/').parse::<u64>();
+    let found = id.ok().and_then(|id| {
+        let store = store().lock().ok()?;
+        store.get(id).map(|c| (c.mime_type, c.bytes.clone()))
+    });
+    let response = match found {
+        Some((mime_type, bytes)) => Response::builder()
+            .header(header::CONTENT_TYPE, mime_type)
+            // The app's own origin differs from the scheme's, and `fetch`
+            // needs this to read the bytes.
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .body(Cow::Owned(bytes)),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Cow::Borrowed(&[][..])),
+    };
+    response.expect("static response parts are valid")
+}
+
+/// Applies `options` to an image captured from `area` and encodes it.
+pub fn encode(
+    mut image: RgbaImage,
+    area: CaptureArea,
+    options: &CaptureOptions,
+) -> Result<CaptureOutput, QuackError> {
+    let (original_width, original_height) = image.dimensions();
+    let redactions = match &options.redact {
+        Some(redaction) => redact::apply(native(), &mut image, area, redaction)?,
+        None => Vec::new(),
+    };
+    let mut image = DynamicImage::ImageRgba8(image);
+    if let Some(max_edge) = options.max_edge.filter(|&edge| edge > 0) {
+        if original_width.max(original_height) > max_edge {
+            // Keeps the aspect ratio; Lanczos keeps small text legible.
+            image = image.resize(max_edge, max_edge, FilterType::Lanczos3);
+        }
+    }
+    if options.grayscale {
+        image = DynamicImage::ImageLuma8(image.to_luma8());
+    }
+
+    let output_format = match options.format {
+        OutputFormat::Png => ImageOutputFormat::Png,
+        OutputFormat::Jpeg => ImageOutputFormat::Jpeg(options.quality.clamp(1, 100)),
+        OutputFormat::Webp => ImageOutputFormat::WebP,
+    };
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut io::Cursor::new(&mut bytes), output_format)
+        .map_err(|_| QuackError::EncodeFailed {
+            format: options.format.name().to_string(),
+        })?;
+
+    let mime_type = options.format.mime_type();
+    let byte_len = bytes.len();
+    let id = store().lock()?.insert(mime_type, bytes);
+    Ok(CaptureOutput {
+        id,
+        url: capture_url(id),
+        mime_type,
+        original_width,
+        original_height,
+        width: image.width(),
+        height: image.height(),
+        byte_len,
+        redactions,
+    })
+}
@@ -0,0 +1,408 @@
+//! Blacking out sensitive parts of a screenshot before it leaves the machine.
+//!
+//! When [`CaptureOptions::redact`](super::CaptureOptions) is set, the captured
+//! image is painted over where it shows Quack's own windows, windows matching
+//! the [`RedactionPolicy`] deny-list, and any rectangles the caller passes.
+//! What was hidden comes back as a list of [`Redaction`]s.
+
+use crate::error::QuackError;
+use crate::injection::policy::{Target, TargetRule};
+use crate::platform::{Platform, WindowBounds, WindowId};
+use image::{Rgba, RgbaImage};
+use std::sync::{Mutex, OnceLock};
+
+const REDACTED: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct RedactionOptions {
+    /// Hide Quack's own overlay, chat and other windows.
+    pub own_windows: bool,
+    /// Hide windows matching the redaction deny-list.
+    pub denied_windows: bool,
+    /// Extra areas to hide, in pixels of the captured image.
+    pub rects: Vec<WindowBounds>,
+}
+
+impl Default for RedactionOptions {
+    fn default() -> Self {
+        RedactionOptions {
+            own_windows: true,
+            denied_windows: true,
+            rects: Vec::new(),
+        }
+    }
+}
+
+/// Which windows are always hidden from screenshots.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RedactionPolicy {
+    pub deny: Vec<TargetRule>,
+}
+
+impl Default for RedactionPolicy {
+    /// Password managers. Banking sites usually live in a browser tab, so
+    /// they need `Title` rules naming the bank.
+    fn default() -> Self {
+        RedactionPolicy {
+            deny: vec![
+                TargetRule::process("KeePass"),
+                TargetRule::process("KeePassXC"),
+                TargetRule::process("1Password"),
+                TargetRule::process("Bitwarden"),
+                TargetRule::process("Dashlane"),
+                TargetRule::class("keepassxc"),
+                TargetRule::class("1password"),
+                TargetRule::class("bitwarden"),
+            ],
+        }
+    }
+}
+
+/// The process-wide redaction policy.
+pub fn policy() -> &'static Mutex<RedactionPolicy> {
+    static POLICY: OnceLock<Mutex<RedactionPolicy>> = OnceLock::new();
+    POLICY.get_or_init(|| Mutex::new(RedactionPolicy::default()))
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionReason {
+    OwnWindow,
+    DeniedWindow,
+    Requested,
+}
+
+/// An area that was blacked out.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Redaction {
+    /// In pixels of the captured image, before any downscaling.
+    pub rect: WindowBounds,
+    pub reason: RedactionReason,
+    /// Title of the hidden window, for window redactions.
+    pub title: Option<String>,
+}
+
+/// What a captured image shows.
+#[derive(Clone, Copy, Debug)]
+pub enum CaptureArea {
+    /// Everything on screen within these bounds.
+    Screen(WindowBounds),
+    /// A single window's own contents, without what overlaps it.
+    Window(WindowId),
+    /// Nothing on screen, e.g. an image copied to the clipboard. Only the
+    /// requested rectangles apply.
+    Detached,
+}
+
+/// Why `window` has to be hidden, if it does.
+fn reason_for(
+    platform: &impl Platform,
+    window: WindowId,
+    options: &RedactionOptions,
+    deny: &[TargetRule],
+) -> Option<RedactionReason> {
+    if options.own_windows && platform.window_pid(window) == Some(std::process::id()) {
+        return Some(RedactionReason::OwnWindow);
+    }
+    if options.denied_windows && !deny.is_empty() {
+        let target = Target::of(platform, window);
+        if deny.iter().any(|rule| rule.matches(&target)) {
+            return Some(RedactionReason::DeniedWindow);
+        }
+    }
+    None
+}
+
+/// `rect` clipped to an image of `width` × `height`.
+fn clip(rect: WindowBounds, width: u32, height: u32) -> Option<WindowBounds> {
+    let left = rect.x.max(0);
+    let top = rect.y.max(0);
+    let right = (rect.x + rect.width as i32).min(width as i32);
+    let bottom = (rect.y + rect.height as i32).min(height as i32);
+    (right > left && bottom > top).then(|| WindowBounds {
+        x: left,
+        y: top,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+/// Blacks out what `options` asks for and returns the redaction map.
+pub fn apply(
+    platform: &impl Platform,
+    image: &mut RgbaImage,
+    area: CaptureArea,
+    options: &RedactionOptions,
+) -> Result<Vec<Redaction>, QuackError> {
+    let (width, height) = image.dimensions();
+    let deny = policy().lock()?.deny.clone();
+    let mut redactions = Vec::new();
+
+    match area {
+        CaptureArea::Window(window) => {
+            if let Some(reason) = reason_for(platform, window, options, &deny) {
+                redactions.push(Redaction {
+                    rect: WindowBounds {
+                        x: 0,
+                        y: 0,
+                        width,
+                        height,
+                    },
+                    reason,
+                    title: Some(platform.window_title(window)),
+                });
+            }
+        }
+        CaptureArea::Screen(bounds) => {
+            for window in platform.list_windows().into_iter().filter(|w| !w.cloaked) {
+                let Some(reason) = reason_for(platform, window.id, options, &deny) else {
+                    continue;
+                };
+                let relative = WindowBounds {
+                    x: window.bounds.x - bounds.x,
+                    y: window.bounds.y - bounds.y,
+                    ..window.bounds
+                };
+                if let Some(rect) = clip(relative, width, height) {
+                    redactions.push(Redaction {
+                        rect,
+                        reason,
+                        title: Some(platform.window_title(window.id)),
+                    });
+                }
+            }
+        }
+        CaptureArea::Detached => {}
+    }
+    redactions.extend(
+        options
+            .rects
+            .iter()
+            .filter_map(|&rect| clip(rect, width, height))
+            .map(|rect| Redaction {
+                rect,
+                reason: RedactionReason::Requested,
+                title: None,
+            }),
+    );
+
+    for redaction in &redactions {
+        let rect = redaction.rect;
+        for y in rect.y as u32..rect.y as u32 + rect.height {
+            for x in rect.x as u32..rect.x as u32 + rect.width {
+                image.put_pixel(x, y, REDACTED);
+            }
+        }
+    }
+    Ok(redactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injection::{plan::InputEvent, PasteContent};
+    use crate::platform::{MonitorInfo, NativeWindow, ScreenCapture, ScreenTarget};
+    use std::path::PathBuf;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    /// Windows in z-order with their screen bounds and owning pid. The id of
+    /// each is its position plus one; only what redaction reads is implemented.
+    struct FakePlatform(Vec<(WindowBounds, u32)>);
+
+    impl FakePlatform {
+        /// Every window belongs to Quack, so each one gets redacted.
+        fn own(bounds: &[WindowBounds]) -> Self {
+            FakePlatform(bounds.iter().map(|&b| (b, std::process::id())).collect())
+        }
+
+        fn get(&self, window: WindowId) -> (WindowBounds, u32) {
+            self.0[window.as_raw() as usize - 1]
+        }
+    }
+
+    impl Platform for FakePlatform {
+        fn foreground_window(&self) -> Option<WindowId> {
+            unimplemented!()
+        }
+        fn list_windows(&self) -> Vec<NativeWindow> {
+            (0..self.0.len())
+                .map(|i| NativeWindow {
+                    id: WindowId::from_raw(i as isize + 1).unwrap(),
+                    bounds: self.0[i].0,
+                    cloaked: false,
+                    tool_window: false,
+                })
+                .collect()
+        }
+        fn window_title(&self, window: WindowId) -> String {
+            format!("Window {}", window.as_raw())
+        }
+        fn window_pid(&self, window: WindowId) -> Option<u32> {
+            Some(self.get(window).1)
+        }
+        fn window_class(&self, _window: WindowId) -> Option<String> {
+            None
+        }
+        fn exe_path(&self, _window: WindowId) -> Option<PathBuf> {
+            None
+        }
+        fn window_icon_base64(&self, _window: WindowId) -> Option<String> {
+            unimplemented!()
+        }
+        fn watch_foreground(
+            &self,
+            _on_change: &mut dyn FnMut(Option<WindowId>, String),
+        ) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn focus_window(&self, _window: WindowId) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn send_input(&self, _events: &[InputEvent]) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn is_elevated(&self, _window: WindowId) -> bool {
+            unimplemented!()
+        }
+        fn on_secure_desktop(&self) -> bool {
+            unimplemented!()
+        }
+        fn inject_text(&self, _window: WindowId, _text: &str) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn paste_text(&self, _window: WindowId, _content: &PasteContent) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError> {
+            unimplemented!()
+        }
+        fn capture_screen(&self, _target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+            unimplemented!()
+        }
+        fn capture_region(&self, _bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+        fn capture_window(&self, _window: WindowId) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> WindowBounds {
+        WindowBounds {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn redact(
+        platform: &FakePlatform,
+        area: CaptureArea,
+        rects: &[WindowBounds],
+    ) -> (RgbaImage, Vec<WindowBounds>) {
+        let mut image = RgbaImage::from_pixel(10, 8, WHITE);
+        let options = RedactionOptions {
+            rects: rects.to_vec(),
+            ..Default::default()
+        };
+        let redactions = apply(platform, &mut image, area, &options).unwrap();
+        (image, redactions.into_iter().map(|r| r.rect).collect())
+    }
+
+    /// Checks that exactly the pixels inside `rects` were blacked out.
+    fn assert_painted(image: &RgbaImage, rects: &[WindowBounds]) {
+        for (x, y, &pixel) in image.enumerate_pixels() {
+            let inside = rects.iter().any(|r| {
+                (r.x..r.x + r.width as i32).contains(&(x as i32))
+                    && (r.y..r.y + r.height as i32).contains(&(y as i32))
+            });
+            assert_eq!(
+                pixel,
+                if inside { REDACTED } else { WHITE },
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn maps_window_rects_into_the_capture_area() {
+        // The 10×8 capture shows the screen from (100, 50).
+        let area = CaptureArea::Screen(rect(100, 50, 10, 8));
+        let cases = [
+            // Fully inside.
+            (rect(102, 51, 3, 2), Some(rect(2, 1, 3, 2))),
+            // Sticking out to the top left.
+            (rect(95, 45, 7, 8), Some(rect(0, 0, 2, 3))),
+            // Sticking out to the bottom right.
+            (rect(108, 56, 5, 5), Some(rect(8, 6, 2, 2))),
+            // Covering the whole capture.
+            (rect(0, 0, 500, 500), Some(rect(0, 0, 10, 8))),
+            // Entirely outside, or only touching the edge.
+            (rect(0, 0, 50, 50), None),
+            (rect(110, 50, 5, 5), None),
+            (rect(100, 58, 5, 5), None),
+        ];
+        for (window, expected) in cases {
+            let (image, rects) = redact(&FakePlatform::own(&[window]), area, &[]);
+            assert_eq!(
+                rects,
+                expected.into_iter().collect::<Vec<_>>(),
+                "{:?}",
+                window
+            );
+            assert_painted(&image, &rects);
+        }
+    }
+
+    #[test]
+    fn clamps_requested_rects_to_the_image() {
+        let cases = [
+            (rect(1, 1, 2, 2), Some(rect(1, 1, 2, 2))),
+            (rect(-3, 6, 5, 5), Some(rect(0, 6, 2, 2))),
+            (rect(9, -1, 4, 3), Some(rect(9, 0, 1, 2))),
+            (rect(10, 0, 1, 1), None),
+            (rect(-5, -5, 5, 5), None),
+            (rect(2, 2, 0, 3), None),
+        ];
+        for (requested, expected) in cases {
+            let (image, rects) = redact(
+                &FakePlatform(Vec::new()),
+                CaptureArea::Detached,
+                &[requested],
+            );
+            assert_eq!(
+                rects,
+                expected.into_iter().collect::<Vec<_>>(),
+                "{:?}",
+                requested
+            );
+            assert_painted(&image, &rects);
+        }
+    }
+
+    #[test]
+    fn redacts_only_matching_windows() {
+        let platform = FakePlatform(vec![
+            (rect(0, 0, 3, 3), std::process::id()),
+            (rect(5, 5, 3, 3), std::process::id() + 1),
+        ]);
+        let (image, rects) = redact(&platform, CaptureArea::Screen(rect(0, 0, 10, 8)), &[]);
+        assert_eq!(rects, [rect(0, 0, 3, 3)]);
+        assert_painted(&image, &rects);
+    }
+
+    #[test]
+    fn hides_a_whole_window_capture_of_an_own_window() {
+        let platform = FakePlatform::own(&[rect(500, 500, 40, 40)]);
+        let window = WindowId::from_raw(1).unwrap();
+        let (image, rects) = redact(&platform, CaptureArea::Window(window), &[rect(0, 0, 1, 1)]);
+        assert_eq!(rects, [rect(0, 0, 10, 8), rect(0, 0, 1, 1)]);
+        assert_painted(&image, &rects);
+    }
+}
@@ -0,0 +1,484 @@
+//! Recently copied text, so any of it can be sent to Quack again.
+//!
+//! The clipboard watcher records every text copy here along with the window it
+//! was copied from. Copying the same text again moves the existing clip to the
+//! front instead of adding a duplicate. Pinned clips are never evicted. When a
+//! disk path is configured, the history is also written there as JSON after
+//! every change so it survives restarts.
+
+use crate::error::QuackError;
+use crate::icon_cache;
+use crate::platform::Platform;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of unpinned clips kept.
+const DEFAULT_CAPACITY: usize = 100;
+/// Longer copies aren't recorded; they're rarely worth resending and would
+/// bloat the history file.
+const MAX_TEXT_BYTES: usize = 256 * 1024;
+/// Number of results `search` returns unless told otherwise.
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// The window a clip was copied from.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClipSource {
+    pub title: String,
+    pub process_name: Option<String>,
+    /// Key to pass to `get_app_icon` to fetch the app icon.
+    pub icon_key: Option<String>,
+}
+
+impl ClipSource {
+    /// Describes the foreground window, which is where the copy happened.
+    fn foreground(platform: &impl Platform) -> Option<Self> {
+        let window = platform.foreground_window()?;
+        Some(ClipSource {
+            title: platform.window_title(window),
+            process_name: platform
+                .exe_path(window)
+                .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned())),
+            icon_key: icon_cache::icon_key_for_window(platform, window),
+        })
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Clip {
+    pub id: u64,
+    pub text: String,
+    /// FNV-1a hash of the text as 16 hex digits, used to spot duplicates.
+    pub hash: String,
+    /// Milliseconds since the Unix epoch of the first copy.
+    pub first_copied: u64,
+    /// Milliseconds since the Unix epoch of the latest copy or restore.
+    pub last_copied: u64,
+    pub copy_count: u32,
+    pub pinned: bool,
+    /// Where the latest copy came from.
+    pub source: Option<ClipSource>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// FNV-1a, stable across runs so hashes in the history file stay valid.
+fn content_hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Scores `haystack` against one lowercase query term, or `None` if it
+/// doesn't match. A substring beats a scattered subsequence; characters
+/// matched in a run or at the start of a word score higher.
+fn term_score(term: &str, haystack: &str) -> Option<u32> {
+    if let Some(pos) = haystack.find(term) {
+        return Some(2000 + 10 * term.len() as u32 - pos.min(1000) as u32);
+    }
+    let mut wanted = term.chars().peekable();
+    let mut score = 0;
+    let mut run = 0;
+    let mut prev: Option<char> = None;
+    for c in haystack.chars() {
+        let Some(&want) = wanted.peek() else { break };
+        if c == want {
+            wanted.next();
+            run += 1;
+            score += 1 + 2 * run;
+            if prev.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 5;
+            }
+        } else {
+            run = 0;
+        }
+        prev = Some(c);
+    }
+    wanted.peek().is_none().then_some(score)
+}
+
+impl Clip {
+    /// Fuzzy-matches every term of `query` against the text, falling back to
+    /// the source window's title and app name.
+    fn score(&self, terms: &[String]) -> Option<u32> {
+        let text = self.text.to_lowercase();
+        let source = self
+            .source
+            .as_ref()
+            .map(|s| {
+                format!("{} {}", s.title, s.process_name.as_deref().unwrap_or("")).to_lowercase()
+            })
+            .unwrap_or_default();
+        terms.iter().try_fold(0, |total, term| {
+            let score =
+                term_score(term, &text).or_else(|| term_score(term, &source).map(|s| s / 2))?;
+            Some(total + score)
+        })
+    }
+}
+
+pub struct ClipboardHistory {
+    capacity: usize,
+    /// Most recently copied first.
+    clips: VecDeque<Clip>,
+    next_id: u64,
+    disk_path: Option<PathBuf>,
+}
+
+impl ClipboardHistory {
+    pub fn new(capacity: usize) -> Self {
+        ClipboardHistory {
+            capacity: capacity.max(1),
+            clips: VecDeque::new(),
+            next_id: 1,
+            disk_path: None,
+        }
+    }
+
+    /// Persists the history to `path`, merging in what was saved there
+    /// before. `None` stops persisting and deletes the file, since it may
+    /// hold things the user copied in confidence.
+    pub fn set_disk_path(&mut self, path: Option<PathBuf>) -> Result<(), QuackError> {
+        let Some(path) = path else {
+            if let Some(old) = self.disk_path.take() {
+                let _ = std::fs::remove_file(old);
+            }
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(QuackError::os)?;
+        }
+        let saved: Vec<Clip> = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let current = std::mem::replace(&mut self.clips, saved.into());
+        self.next_id = self.clips.iter().map(|c| c.id + 1).max().unwrap_or(1);
+        // Replay this session's clips on top, oldest first, so they dedup
+        // against the saved ones and keep their order.
+        for clip in current.into_iter().rev() {
+            let pinned = clip.pinned;
+            let merged = self.insert(clip.text, clip.source, clip.last_copied);
+            merged.pinned |= pinned;
+        }
+        self.disk_path = Some(path);
+        self.evict();
+        self.save()
+    }
+
+    pub fn disk_path(&self) -> Option<&PathBuf> {
+        self.disk_path.as_ref()
+    }
+
+    /// Adds a copy of `text`, or moves an earlier copy of it to the front.
+    pub fn record(&mut self, text: String, source: Option<ClipSource>) -> Option<Clip> {
+        if text.is_empty() || text.len() > MAX_TEXT_BYTES {
+            return None;
+        }
+        let clip = self.insert(text, source, now_millis()).clone();
+        self.evict();
+        let _ = self.save();
+        Some(clip)
+    }
+
+    fn insert(&mut self, text: String, source: Option<ClipSource>, copied: u64) -> &mut Clip {
+        let hash = content_hash(&text);
+        let existing = self
+            .clips
+            .iter()
+            .position(|c| c.hash == hash && c.text == text);
+        let clip = match existing.and_then(|pos| self.clips.remove(pos)) {
+            Some(mut clip) => {
+                clip.last_copied = copied;
+                clip.copy_count += 1;
+                clip.source = source.or(clip.source);
+                clip
+            }
+            None => {
+                self.next_id += 1;
+                Clip {
+                    id: self.next_id - 1,
+                    text,
+                    hash,
+                    first_copied: copied,
+                    last_copied: copied,
+                    copy_count: 1,
+                    pinned: false,
+                    source,
+                }
+            }
+        };
+        self.clips.push_front(clip);
+        &mut self.clips[0]
+    }
+
+    /// Drops the oldest unpinned clips beyond the capacity.
+    fn evict(&mut self) {
+        let mut unpinned = self.clips.iter().filter(|c| !c.pinned).count();
+        while unpinned > self.capacity {
+            if let Some(pos) = self.clips.iter().rposition(|c| !c.pinned) {
+                self.clips.remove(pos);
+            }
+            unpinned -= 1;
+        }
+    }
+
+    fn save(&self) -> Result<(), QuackError> {
+        let Some(path) = &self.disk_path else {
+            return Ok(());
+        };
+        let bytes = serde_json::to_vec(&self.clips).map_err(QuackError::os)?;
+        std::fs::write(path, bytes).map_err(QuackError::os)
+    }
+
+    fn position(&self, id: u64) -> Result<usize, QuackError> {
+        self.clips
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or_else(|| QuackError::invalid_argument(format!("No clip with id {}", id)))
+    }
+
+    /// The clip with the given id, leaving its place in the history alone.
+    pub fn get(&self, id: u64) -> Result<Clip, QuackError> {
+        Ok(self.clips[self.position(id)?].clone())
+    }
+
+    /// All clips, most recently copied first.
+    pub fn list(&self) -> Vec<Clip> {
+        self.clips.iter().cloned().collect()
+    }
+
+    /// Clips matching `query` (whitespace-separated terms), best match first.
+    /// Ties go to the more recent clip.
+    pub fn search(&self, query: &str, limit: Option<usize>) -> Vec<Clip> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut matches: Vec<(u32, &Clip)> = self
+            .clips
+            .iter()
+            .filter_map(|clip| Some((clip.score(&terms)?, clip)))
+            .collect();
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        matches
+            .into_iter()
+            .take(limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+            .map(|(_, clip)| clip.clone())
+            .collect()
+    }
+
+    pub fn pin(&mut self, id: u64, pinned: bool) -> Result<Clip, QuackError> {
+        let pos = self.position(id)?;
+        self.clips[pos].pinned = pinned;
+        let clip = self.clips[pos].clone();
+        self.evict();
+        self.save()?;
+        Ok(clip)
+    }
+
+    /// Moves a clip to the front as if it had just been copied again.
+    pub fn restore(&mut self, id: u64) -> Result<Clip, QuackError> {
+        let pos = self.position(id)?;
+        let mut clip = self.clips.remove(pos).expect("position is in range");
+        clip.last_copied = now_millis();
+        self.clips.push_front(clip.clone());
+        self.save()?;
+        Ok(clip)
+    }
+}
+
+/// The process-wide clipboard history.
+pub fn history() -> &'static Mutex<ClipboardHistory> {
+    static HISTORY: OnceLock<Mutex<ClipboardHistory>> = OnceLock::new();
+    HISTORY.get_or_init(|| Mutex::new(ClipboardHistory::new(DEFAULT_CAPACITY)))
+}
+
+/// Records text just copied in the foreground window.
+pub fn record(platform: &impl Platform, text: String) -> Option<Clip> {
+    // Resolve the source outside the lock; icon lookups can take a while.
+    let source = ClipSource::foreground(platform);
+    history().lock().ok()?.record(text, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A step in building up a history.
+    enum Step {
+        Copy(&'static str),
+        Pin(&'static str),
+        Unpin(&'static str),
+    }
+    use Step::{Copy, Pin, Unpin};
+
+    fn run(capacity: usize, steps: &[Step]) -> ClipboardHistory {
+        let mut history = ClipboardHistory::new(capacity);
+        for step in steps {
+            match *step {
+                Copy(text) => {
+                    history.record(text.to_string(), None).expect("recorded");
+                }
+                Pin(text) | Unpin(text) => {
+                    let id = history.clips.iter().find(|c| c.text == text).unwrap().id;
+                    history.pin(id, matches!(step, Pin(_))).unwrap();
+                }
+            }
+        }
+        history
+    }
+
+    fn texts(history: &ClipboardHistory) -> Vec<&str> {
+        history.clips.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn repeat_copies_move_to_the_front() {
+        let cases: [(&[Step], &[&str], u32); 4] = [
+            (&[Copy("a"), Copy("b"), Copy("c")], &["c", "b", "a"], 1),
+            (&[Copy("a"), Copy("b"), Copy("a")], &["a", "b"], 2),
+            (&[Copy("a"), Copy("a"), Copy("a")], &["a"], 3),
+            // Duplicates are matched case-sensitively.
+            (&[Copy("a"), Copy("A")], &["A", "a"], 1),
+        ];
+        for (steps, expected, front_count) in cases {
+            let history = run(10, steps);
+            assert_eq!(texts(&history), expected);
+            assert_eq!(history.clips[0].copy_count, front_count, "{:?}", expected);
+        }
+    }
+
+    #[test]
+    fn keeps_ids_when_moving_to_the_front() {
+        let mut history = ClipboardHistory::new(10);
+        let first = history.record("a".to_string(), None).unwrap();
+        history.record("b".to_string(), None);
+        let again = history.record("a".to_string(), None).unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(again.first_copied, first.first_copied);
+    }
+
+    #[test]
+    fn evicts_the_oldest_unpinned_clips() {
+        let cases: [(usize, &[Step], &[&str]); 4] = [
+            (2, &[Copy("a"), Copy("b"), Copy("c")], &["c", "b"]),
+            (
+                2,
+                &[Copy("a"), Pin("a"), Copy("b"), Copy("c"), Copy("d")],
+                &["d", "c", "a"],
+            ),
+            (
+                1,
+                &[Copy("a"), Pin("a"), Copy("b"), Pin("b"), Copy("c")],
+                &["c", "b", "a"],
+            ),
+            // Unpinning counts the clip against the capacity again.
+            (
+                2,
+                &[Copy("a"), Pin("a"), Copy("b"), Copy("c"), Unpin("a")],
+                &["c", "b"],
+            ),
+        ];
+        for (capacity, steps, expected) in cases {
+            assert_eq!(texts(&run(capacity, steps)), expected);
+        }
+    }
+
+    #[test]
+    fn skips_empty_and_oversized_copies() {
+        let mut history = ClipboardHistory::new(10);
+        assert!(history.record(String::new(), None).is_none());
+        assert!(history
+            .record("x".repeat(MAX_TEXT_BYTES + 1), None)
+            .is_none());
+        assert!(history.clips.is_empty());
+    }
+
+    #[test]
+    fn ranks_substrings_over_scattered_matches() {
+        // (term, better, worse)
+        let cases = [
+            ("cat", "concatenate", "c-a-t"),
+            ("cat", "cat food", "the cat"),
+            ("gc", "git checkout", "magic"),
+            ("gr", "good grief", "bigger"),
+        ];
+        for (term, better, worse) in cases {
+            let (better_score, worse_score) = (term_score(term, better), term_score(term, worse));
+            assert!(
+                better_score > worse_score,
+                "{:?}: {:?} ({:?}) should beat {:?} ({:?})",
+                term,
+                better,
+                better_score,
+                worse,
+                worse_score
+            );
+        }
+        for (term, haystack) in [("cat", "act"), ("xyz", "x y"), ("ab", "")] {
+            assert_eq!(
+                term_score(term, haystack),
+                None,
+                "{:?} in {:?}",
+                term,
+                haystack
+            );
+        }
+    }
+
+    #[test]
+    fn searches_the_source_window_as_a_fallback() {
+        let mut history = ClipboardHistory::new(10);
+        let source = ClipSource {
+            title: "Invoice - Mail".to_string(),
+            process_name: Some("thunderbird".to_string()),
+            icon_key: None,
+        };
+        history.record("invoice number 42".to_string(), None);
+        history.record("42".to_string(), Some(source));
+        history.record("unrelated".to_string(), None);
+        let found: Vec<String> = history
+            .search("invoice", None)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(found, ["invoice number 42", "42"]);
+        assert_eq!(history.search("thunderbird 42", None).len(), 1);
+    }
+
+    #[test]
+    fn merges_the_saved_history_when_setting_a_disk_path() {
+        let dir = std::env::temp_dir().join(format!("quack-history-{}", std::process::id()));
+        let path = dir.join("history.json");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut saved = run(10, &[Copy("old"), Copy("shared"), Pin("old")]);
+        saved.set_disk_path(Some(path.clone())).unwrap();
+        let saved_ids: Vec<u64> = saved.clips.iter().map(|c| c.id).collect();
+
+        let mut session = run(10, &[Copy("shared"), Copy("new"), Pin("new")]);
+        session.set_disk_path(Some(path.clone())).unwrap();
+        assert_eq!(texts(&session), ["new", "shared", "old"]);
+        let by_text = |text: &str| session.clips.iter().find(|c| c.text == text).unwrap();
+        assert_eq!(by_text("shared").copy_count, 2);
+        assert_eq!(by_text("shared").id, saved_ids[0]);
+        assert!(by_text("old").pinned && by_text("new").pinned);
+        // New clips get ids past the saved ones.
+        assert!(saved_ids.iter().all(|&id| id < by_text("new").id));
+
+        let on_disk: Vec<Clip> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let on_disk: Vec<&str> = on_disk.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(on_disk, ["new", "shared", "old"]);
+
+        session.set_disk_path(None).unwrap();
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,211 @@
+//! The error type returned by every command.
+//!
+//! A [`QuackError`] reaches the frontend as `{ code, message, details }`:
+//! `code` is a stable snake_case identifier to branch on, `message` is meant
+//! for humans, and `details` holds the variant's fields (or `null`).
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum QuackError {
+    /// No window matches the given title or selector.
+    WindowNotFound { selector: String },
+    /// Several windows match and the caller has to narrow it down.
+    AmbiguousWindow {
+        selector: String,
+        candidates: Vec<String>,
+    },
+    /// A malformed argument, e.g. a null handle or a bad regex.
+    InvalidArgument { message: String },
+    /// The command doesn't fit the current state, e.g. no injection running.
+    InvalidState { message: String },
+    /// The OS refused, e.g. UIPI dropped our input or focus couldn't be taken.
+    AccessDenied { message: String },
+    /// The injection policy's deny- or allow-list rules out the target.
+    InjectionDenied { target: String, reason: String },
+    /// The target runs with more privileges than a regular user's.
+    ElevatedTarget { target: String },
+    /// A UAC prompt, lock screen or similar currently has the keyboard.
+    SecureDesktop,
+    /// The target needs the user's approval. Retry with `token` once given.
+    ConfirmationRequired { token: u64, target: String },
+    /// A step of a screen or window capture failed.
+    CaptureFailed { stage: String },
+    /// An image couldn't be encoded.
+    EncodeFailed { format: String },
+    /// The feature isn't implemented for this platform.
+    Unsupported { platform: String, feature: String },
+    /// The clipboard couldn't be read or written.
+    ClipboardFailed { message: String },
+    /// Any other failing OS or X11 call.
+    Os { message: String },
+}
+
+impl QuackError {
+    pub fn capture(stage: &str) -> Self {
+        QuackError::CaptureFailed {
+            stage: stage.to_string(),
+        }
+    }
+
+    /// `feature` isn't available on the running platform.
+    pub fn unsupported(feature: &str) -> Self {
+        QuackError::Unsupported {
+            platform: std::env::consts::OS.to_string(),
+            feature: feature.to_string(),
+        }
+    }
+
+    pub fn os(message: impl fmt::Display) -> Self {
+        QuackError::Os {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn clipboard(message: &str) -> Self {
+        QuackError::ClipboardFailed {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl fmt::Display) -> Self {
+        QuackError::InvalidArgument {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn invalid_state(message: &str) -> Self {
+        QuackError::InvalidState {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn access_denied(message: &str) -> Self {
+        QuackError::AccessDenied {
+            message: message.to_string(),
+        }
+    }
+
+    /// Stable identifier sent to the frontend.
+    pub fn code(&self) -> &'static str {
+        match self {
+            QuackError::WindowNotFound { .. } => "window_not_found",
+            QuackError::AmbiguousWindow { .. } => "ambiguous_window",
+            QuackError::InvalidArgument { .. } => "invalid_argument",
+            QuackError::InvalidState { .. } => "invalid_state",
+            QuackError::AccessDenied { .. } => "access_denied",
+            QuackError::InjectionDenied { .. } => "injection_denied",
+            QuackError::ElevatedTarget { .. } => "elevated_target",
+            QuackError::SecureDesktop => "secure_desktop",
+            QuackError::ConfirmationRequired { .. } => "confirmation_required",
+            QuackError::CaptureFailed { .. } => "capture_failed",
+            QuackError::EncodeFailed { .. } => "encode_failed",
+            QuackError::Unsupported { .. } => "unsupported",
+            QuackError::ClipboardFailed { .. } => "clipboard_failed",
+            QuackError::Os { .. } => "os_error",
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            QuackError::WindowNotFound { selector } => json!({ "selector": selector }),
+            QuackError::AmbiguousWindow {
+                selector,
+                candidates,
+            } => json!({ "selector": selector, "candidates": candidates }),
+            QuackError::InjectionDenied { target, reason } => {
+                json!({ "target": target, "reason": reason })
+            }
+            QuackError::ElevatedTarget { target } => json!({ "target": target }),
+            QuackError::ConfirmationRequired { token, target } => {
+                json!({ "token": token, "target": target })
+            }
+            QuackError::CaptureFailed { stage } => json!({ "stage": stage }),
+            QuackError::EncodeFailed { format } => json!({ "format": format }),
+            QuackError::Unsupported { platform, feature } => {
+                json!({ "platform": platform, "feature": feature })
+            }
+            QuackError::InvalidArgument { .. }
+            | QuackError::InvalidState { .. }
+            | QuackError::AccessDenied { .. }
+            | QuackError::SecureDesktop
+            | QuackError::ClipboardFailed { .. }
+            | QuackError::Os { .. } => Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for QuackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuackError::WindowNotFound { selector } => write!(f, "No window matches {}", selector),
+            QuackError::AmbiguousWindow {
+                selector,
+                candidates,
+            } => write!(
+                f,
+                "Several windows match {}: {}",
+                selector,
+                candidates.join(", ")
+            ),
+            QuackError::InvalidArgument { message }
+            | QuackError::InvalidState { message }
+            | QuackError::AccessDenied { message }
+            | QuackError::ClipboardFailed { message }
+            | QuackError::Os { message } => f.write_str(message),
+            QuackError::InjectionDenied { target, reason } => {
+                write!(f, "Injection into {} is not allowed: {}", target, reason)
+            }
+            QuackError::ElevatedTarget { target } => {
+                write!(f, "Refusing to inject into elevated window {}", target)
+            }
+            QuackError::SecureDesktop => {
+                write!(f, "Refusing to inject while a secure desktop is active")
+            }
+            QuackError::ConfirmationRequired { target, .. } => {
+                write!(f, "Injection into {} needs confirmation", target)
+            }
+            QuackError::CaptureFailed { stage } => write!(f, "Capture failed at {}", stage),
+            QuackError::EncodeFailed { format } => write!(f, "Failed to encode {}", format),
+            QuackError::Unsupported { platform, feature } => {
+                write!(f, "{} is not supported on {}", feature, platform)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuackError {}
+
+impl Serialize for QuackError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("QuackError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("details", &self.details())?;
+        s.end()
+    }
+}
+
+/// A poisoned lock only means another command panicked; report it as an OS
+/// error rather than panicking too.
+impl<T> From<std::sync::PoisonError<T>> for QuackError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        QuackError::os(e)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<x11rb::errors::ConnectionError> for QuackError {
+    fn from(e: x11rb::errors::ConnectionError) -> Self {
+        QuackError::os(format!("X11 connection error: {}", e))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<x11rb::errors::ReplyError> for QuackError {
+    fn from(e: x11rb::errors::ReplyError) -> Self {
+        QuackError::os(format!("X11 request failed: {}", e))
+    }
+}
@@ -1,8 +1,15 @@
+use crate::capture::{self, redact::CaptureArea, CaptureOptions, CaptureOutput};
+use crate::clipboard_history::{self, Clip};
+use crate::error::QuackError;
+use crate::platform::{native, ClipContent, ClipboardWatcher};
+use crate::sensitive::{self, SensitivePolicy};
+#[cfg(target_os = "windows")]
 use enigo::{Enigo, MouseControllable};
 use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 
 static AUTO_SHOW_ON_COPY: AtomicBool = AtomicBool::new(false);
+static CLIPBOARD_HISTORY_ENABLED: AtomicBool = AtomicBool::new(false);
 static CLIPBOARD_WATCHER_RUNNING: AtomicBool = AtomicBool::new(false);
 static AUTO_SHOW_ON_SELECTION: AtomicBool = AtomicBool::new(false);
 static SELECTION_WATCHER_RUNNING: AtomicBool = AtomicBool::new(false);
@@ -10,45 +17,65 @@ static QUACK_WATCHER_ENABLED: AtomicBool = AtomicBool::new(false);
 static QUACK_WATCHER_RUNNING: AtomicBool = AtomicBool::new(false);
 static NOTCH_WINDOW_DISPLAY_ENABLED: AtomicBool = AtomicBool::new(true);
 
-use winapi::um::winuser::{
-    GetAsyncKeyState, GetClipboardSequenceNumber, IsClipboardFormatAvailable, CF_UNICODETEXT,
-    VK_LBUTTON,
-};
-
-unsafe fn read_clipboard_unicode_text() -> Option<String> {
-    use std::ffi::OsString;
-    use std::os::windows::ffi::OsStringExt;
-    use winapi::shared::minwindef::HGLOBAL;
-    use winapi::um::winbase::{GlobalLock, GlobalUnlock};
-    use winapi::um::winuser::{CloseClipboard, GetClipboardData, OpenClipboard};
-    if IsClipboardFormatAvailable(CF_UNICODETEXT) == 0 {
-        return None;
-    }
-    if OpenClipboard(std::ptr::null_mut()) == 0 {
-        return None;
-    }
-    let handle: HGLOBAL = GetClipboardData(CF_UNICODETEXT);
-    if handle.is_null() {
-        CloseClipboard();
-        return None;
-    }
-    let locked = GlobalLock(handle) as *const u16;
-    if locked.is_null() {
-        CloseClipboard();
-        return None;
-    }
-    let mut len: usize = 0;
-    while *locked.add(len) != 0 {
-        len += 1;
+#[cfg(target_os = "windows")]
+use winapi::um::winuser::{GetAsyncKeyState, VK_LBUTTON};
+
+/// Payload of the `clipboard_content_copied` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClipboardContentCopied {
+    Text {
+        text: String,
+    },
+    Html {
+        html: String,
+        text: Option<String>,
+        source_url: Option<String>,
+    },
+    Rtf {
+        rtf: String,
+        text: Option<String>,
+    },
+    /// A PNG served over `quack-capture://`, like screenshots.
+    Image(CaptureOutput),
+    Files {
+        paths: Vec<String>,
+    },
+}
+
+impl ClipboardContentCopied {
+    fn new(content: ClipContent) -> Result<Self, QuackError> {
+        Ok(match content {
+            ClipContent::Text(text) => ClipboardContentCopied::Text { text },
+            ClipContent::Html {
+                fragment,
+                source_url,
+                text,
+            } => ClipboardContentCopied::Html {
+                html: fragment,
+                text,
+                source_url,
+            },
+            ClipContent::Rtf { rtf, text } => ClipboardContentCopied::Rtf { rtf, text },
+            ClipContent::Image(image) => ClipboardContentCopied::Image(capture::encode(
+                image,
+                CaptureArea::Detached,
+                &CaptureOptions::default(),
+            )?),
+            ClipContent::Files(paths) => ClipboardContentCopied::Files {
+                paths: paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect(),
+            },
+        })
     }
-    let slice = std::slice::from_raw_parts(locked, len);
-    let os_string = OsString::from_wide(slice);
-    let text = os_string.to_string_lossy().into_owned();
-    let _ = GlobalUnlock(handle);
-    CloseClipboard();
-    Some(text)
 }
 
+/// Starts the thread behind `clipboard_text_copied`, `clipboard_content_copied`
+/// and the clipboard history. It sleeps until the clipboard changes and exits
+/// at the first change after both features have been turned off. Copies are
+/// passed through the sensitive-data policy before they're recorded or sent.
 fn ensure_clipboard_watcher_started(app: &AppHandle) {
     if CLIPBOARD_WATCHER_RUNNING.swap(true, Ordering::SeqCst) {
         return;
@@ -56,35 +83,58 @@ fn ensure_clipboard_watcher_started(app: &AppHandle) {
     let app_handle = app.clone();
     std::thread::spawn(move || {
         let mut last_text: Option<String> = None;
-        let mut last_seq: u32 = unsafe { GetClipboardSequenceNumber() };
-        loop {
-            if !AUTO_SHOW_ON_COPY.load(Ordering::Relaxed) {
-                CLIPBOARD_WATCHER_RUNNING.store(false, Ordering::SeqCst);
-                break;
+        let result = native().watch_clipboard(&mut |change| {
+            let auto_show = AUTO_SHOW_ON_COPY.load(Ordering::Relaxed);
+            let history = CLIPBOARD_HISTORY_ENABLED.load(Ordering::Relaxed);
+            if !auto_show && !history {
+                return false;
             }
-            let seq_now = unsafe { GetClipboardSequenceNumber() };
-            if seq_now == last_seq {
-                std::thread::sleep(std::time::Duration::from_millis(250));
-                continue;
+            let Ok(policy) = sensitive::policy().lock().map(|p| p.clone()) else {
+                return true;
+            };
+            if change.exclude_from_history && !policy.allows_password_manager_content() {
+                return true;
             }
-            last_seq = seq_now;
-            let current = unsafe { read_clipboard_unicode_text() }
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty());
-            if let Some(ref txt) = current {
-                let is_new = match last_text {
-                    Some(ref prev) => prev != txt,
-                    None => true,
-                };
-                if is_new && txt.len() >= 3 {
-                    crate::functions::overlay::show_magic_dot(app_handle.clone());
-                    let _ = app_handle
-                        .emit("clipboard_text_copied", serde_json::json!({ "text": txt }));
-                    last_text = current.clone();
+            let Some(content) = change.content.and_then(|c| policy.apply_to_content(c)) else {
+                return true;
+            };
+            let txt = content
+                .text()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string);
+            if history && !change.exclude_from_history {
+                if let Some(txt) = &txt {
+                    clipboard_history::record(native(), txt.clone());
                 }
             }
-            std::thread::sleep(std::time::Duration::from_millis(250));
+            if !auto_show {
+                return true;
+            }
+            if txt
+                .as_ref()
+                .is_some_and(|txt| txt.len() < 3 || last_text.as_ref() == Some(txt))
+            {
+                return true;
+            }
+            crate::functions::overlay::show_magic_dot(app_handle.clone());
+            if let Some(txt) = txt {
+                let _ =
+                    app_handle.emit("clipboard_text_copied", serde_json::json!({ "text": txt }));
+                last_text = Some(txt);
+            }
+            match ClipboardContentCopied::new(content) {
+                Ok(payload) => {
+                    let _ = app_handle.emit("clipboard_content_copied", payload);
+                }
+                Err(e) => eprintln!("Failed to read clipboard content: {}", e),
+            }
+            true
+        });
+        if let Err(e) = result {
+            eprintln!("Clipboard watch stopped: {}", e);
         }
+        CLIPBOARD_WATCHER_RUNNING.store(false, Ordering::SeqCst);
     });
 }
 
@@ -149,11 +199,17 @@ fn ensure_selection_watcher_started(app: &AppHandle) {
                                 None => true,
                             };
                             if is_new {
-                                crate::functions::overlay::show_magic_dot(app_handle.clone());
-                                let _ = app_handle.emit(
-                                    "text_selected",
-                                    serde_json::json!({ "text": final_text }),
-                                );
+                                let allowed = sensitive::policy()
+                                    .lock()
+                                    .ok()
+                                    .and_then(|policy| policy.apply(&final_text));
+                                if let Some(allowed) = allowed {
+                                    crate::functions::overlay::show_magic_dot(app_handle.clone());
+                                    let _ = app_handle.emit(
+                                        "text_selected",
+                                        serde_json::json!({ "text": allowed }),
+                                    );
+                                }
                                 last_text = Some(final_text);
                                 last_uia_emit_at = Some(std::time::Instant::now());
                             }
@@ -385,3 +441,95 @@ pub fn set_notch_window_display_enabled(enabled: bool) {
 pub fn get_notch_window_display_enabled() -> bool {
     NOTCH_WINDOW_DISPLAY_ENABLED.load(Ordering::Relaxed)
 }
+
+/// Records every text copy in the clipboard history while enabled.
+#[tauri::command]
+pub fn set_clipboard_history_enabled(app: AppHandle, enabled: bool) {
+    CLIPBOARD_HISTORY_ENABLED.store(enabled, Ordering::Relaxed);
+    if enabled {
+        ensure_clipboard_watcher_started(&app);
+    }
+}
+
+#[tauri::command]
+pub fn get_clipboard_history_enabled() -> bool {
+    CLIPBOARD_HISTORY_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Persists the clipboard history under the app data directory. Disabling it
+/// deletes the saved file.
+#[tauri::command]
+pub fn set_clipboard_history_on_disk_enabled(
+    app: AppHandle,
+    enabled: bool,
+) -> Result<(), QuackError> {
+    let path = if enabled {
+        Some(
+            app.path()
+                .app_data_dir()
+                .map_err(QuackError::os)?
+                .join("clipboard_history.json"),
+        )
+    } else {
+        None
+    };
+    clipboard_history::history().lock()?.set_disk_path(path)
+}
+
+#[tauri::command]
+pub fn get_clipboard_history_on_disk_enabled() -> bool {
+    clipboard_history::history()
+        .lock()
+        .map(|h| h.disk_path().is_some())
+        .unwrap_or(false)
+}
+
+/// Returns the clipboard history, most recent copy first.
+#[tauri::command]
+pub fn list_clipboard_history() -> Result<Vec<Clip>, QuackError> {
+    Ok(clipboard_history::history().lock()?.list())
+}
+
+/// Fuzzy-searches the clipboard history, best match first.
+#[tauri::command]
+pub fn search_clipboard_history(
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<Clip>, QuackError> {
+    Ok(clipboard_history::history().lock()?.search(&query, limit))
+}
+
+/// Pins or unpins a clip. Pinned clips are never evicted.
+#[tauri::command]
+pub fn pin_clip(id: u64, pinned: bool) -> Result<Clip, QuackError> {
+    clipboard_history::history().lock()?.pin(id, pinned)
+}
+
+/// Sends an earlier copy to Quack as if it had just been copied: moves it to
+/// the front of the history and emits `clipboard_text_copied`. The clip is
+/// first checked against the current sensitive-data policy, which may be
+/// stricter than when it was recorded; a held-back clip stays where it is.
+#[tauri::command]
+pub fn restore_clip(app: AppHandle, id: u64) -> Result<Clip, QuackError> {
+    let clip = clipboard_history::history().lock()?.get(id)?;
+    let text = sensitive::policy()
+        .lock()?
+        .apply(&clip.text)
+        .ok_or_else(|| QuackError::invalid_state("Clip holds sensitive data"))?;
+    let clip = clipboard_history::history().lock()?.restore(id)?;
+    crate::functions::overlay::show_magic_dot(app.clone());
+    let _ = app.emit("clipboard_text_copied", serde_json::json!({ "text": text }));
+    Ok(clip)
+}
+
+/// Sets how secrets and personal data in copied or selected text are handled.
+#[tauri::command]
+pub fn set_sensitive_data_policy(policy: SensitivePolicy) -> Result<(), QuackError> {
+    *sensitive::policy().lock()? = policy;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_sensitive_data_policy() -> Result<SensitivePolicy, QuackError> {
+    Ok(sensitive::policy().lock()?.clone())
+}
@@ -1,359 +1,598 @@
-use tauri::{AppHandle, Emitter};
-use crate::platform::{exe_path_from_hwnd, get_icon_base64_from_exe, get_packaged_app_icon_from_hwnd, get_window_icon_base64_from_hwnd, get_window_title};
-use std::{thread, time::Duration};
-use winapi::um::winuser::{GetForegroundWindow, SendInput, INPUT, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_UNICODE};
-use std::os::windows::ffi::OsStrExt;
-use winapi::shared::windef::HWND as WinHWND;
-use winapi::um::winuser::{FindWindowW, SetForegroundWindow};
-use base64::encode;
-use image::{ImageFormat, DynamicImage};
-use std::io;
-use winapi::ctypes::c_void;
-use winapi::um::winuser::{GetDC, ReleaseDC, GetWindowRect, GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN, PrintWindow};
-use winapi::um::wingdi::{CreateCompatibleDC, CreateCompatibleBitmap, SelectObject, BitBlt, GetDIBits, DeleteObject, DeleteDC, SRCCOPY, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS};
-use winapi::shared::windef::RECT;
+use crate::capture::{
+    self,
+    redact::{self, CaptureArea, RedactionPolicy},
+    CaptureOptions, CaptureOutput, OutputFormat,
+};
+use crate::error::QuackError;
+use crate::icon_cache;
+use crate::injection::{
+    self,
+    history::{self, InjectionRecord},
+    policy::InjectionPolicy,
+    session::{InjectionSession, InjectionSummary},
+    InjectionMode, InjectionOptions,
+};
+use crate::platform::{
+    native, MonitorInfo, Platform, ScreenCapture, ScreenTarget, WindowBounds, WindowId,
+};
+use crate::window_selector::WindowSelector;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+static WINDOW_WATCH_RUNNING: AtomicBool = AtomicBool::new(false);
+/// The streaming injection started by `begin_injection`, if any.
+static INJECTION_SESSION: Mutex<Option<InjectionSession>> = Mutex::new(None);
+/// Monitors captured by `start_region_selection`, in monitor order, and the
+/// options to encode the selected region with, until the user picks a region.
+static REGION_SELECTION: Mutex<Option<(Vec<ScreenCapture>, CaptureOptions)>> = Mutex::new(None);
+/// Label prefix of the selection windows, followed by the monitor index.
+const REGION_SELECTOR_LABEL: &str = "region-selector-";
+
+/// Payload of the `active_window_changed` event.
+#[derive(Clone, serde::Serialize)]
+pub struct ActiveWindowChanged {
+    /// Window title, or the executable name when the window has no title.
+    pub name: String,
+    /// Key to pass to `get_app_icon` to fetch the window icon.
+    pub icon_key: Option<String>,
+    pub hwnd: isize,
+    pub pid: Option<u32>,
+    pub exe_path: Option<String>,
+    pub process_name: Option<String>,
+    pub class: Option<String>,
+}
+
+impl ActiveWindowChanged {
+    fn describe(platform: &impl Platform, window: WindowId, title: String) -> Self {
+        let exe_path = platform.exe_path(window);
+        let mut name = title;
+        if name.is_empty() {
+            if let Some(exe_path) = &exe_path {
+                name = exe_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_string();
+            }
+        }
+        ActiveWindowChanged {
+            name,
+            icon_key: icon_cache::icon_key_for_window(platform, window),
+            hwnd: window.as_raw(),
+            pid: platform.window_pid(window),
+            process_name: exe_path
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .map(|s| s.to_string_lossy().into_owned()),
+            exe_path: exe_path.map(|p| p.to_string_lossy().into_owned()),
+            class: platform.window_class(window),
+        }
+    }
+}
 
+/// Starts emitting `active_window_changed` whenever the focused window or its
+/// title changes. Only one watcher thread is ever started.
 #[tauri::command]
 pub fn start_window_watch(app: AppHandle) {
-	thread::spawn(move || loop {
-		unsafe {
-			let hwnd = GetForegroundWindow();
-			if !hwnd.is_null() {
-				let icon_base64 = get_window_icon_base64_from_hwnd(hwnd)
-					.or_else(|| get_packaged_app_icon_from_hwnd(hwnd))
-					.or_else(|| {
-						exe_path_from_hwnd(hwnd).and_then(|p| get_icon_base64_from_exe(&p))
-					});
-				if let Some(icon_base64) = icon_base64 {
-					let mut app_name = get_window_title(hwnd);
-					if app_name.is_empty() {
-						if let Some(exe_path) = exe_path_from_hwnd(hwnd) {
-							app_name = exe_path
-								.file_stem()
-								.and_then(|s| s.to_str())
-								.unwrap_or("")
-								.to_string();
-						}
-					}
-
-					let _ = app.emit(
-						"active_window_changed",
-						serde_json::json!({
-							"name": app_name,
-							"icon": format!("data:image/png;base64,{}", icon_base64),
-							"hwnd": hwnd as isize,
-						}),
-					);
-				}
-			}
-		}
-		thread::sleep(Duration::from_secs(1));
-	});
+    if WINDOW_WATCH_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || {
+        let platform = native();
+        let mut last: Option<(WindowId, String)> = None;
+        let result = platform.watch_foreground(&mut |window, title| {
+            let Some(window) = window else {
+                // Nothing focused, so whichever window comes next is a change,
+                // even the one focused before.
+                last = None;
+                return;
+            };
+            if last
+                .as_ref()
+                .is_some_and(|(w, t)| *w == window && *t == title)
+            {
+                return;
+            }
+            last = Some((window, title.clone()));
+            let _ = app.emit(
+                "active_window_changed",
+                ActiveWindowChanged::describe(platform, window, title),
+            );
+        });
+        if let Err(e) = result {
+            eprintln!("Window watch stopped: {}", e);
+        }
+        WINDOW_WATCH_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Returns the icon for an `icon_key` from `active_window_changed` as a
+/// `data:image/png;base64,...` URL.
+#[tauri::command]
+pub fn get_app_icon(key: String) -> Option<String> {
+    icon_cache::get_icon(&key).map(|icon| format!("data:image/png;base64,{}", icon))
+}
+
+/// Persists cached icons under the app data directory so they survive restarts.
+#[tauri::command]
+pub fn set_icon_cache_on_disk_enabled(app: AppHandle, enabled: bool) -> Result<(), QuackError> {
+    let dir = if enabled {
+        Some(
+            app.path()
+                .app_data_dir()
+                .map_err(QuackError::os)?
+                .join("icons"),
+        )
+    } else {
+        None
+    };
+    icon_cache::cache().lock()?.set_disk_dir(dir);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_icon_cache_on_disk_enabled() -> bool {
+    icon_cache::cache()
+        .lock()
+        .map(|c| c.disk_dir().is_some())
+        .unwrap_or(false)
+}
+
+/// Resolves the target of a command that takes either a plain window title
+/// (matched exactly, as before) or a [`WindowSelector`].
+fn select_window(
+    window_title: Option<String>,
+    selector: Option<WindowSelector>,
+) -> Result<WindowId, QuackError> {
+    let selector = selector
+        .or(window_title.map(WindowSelector::Title))
+        .ok_or_else(|| {
+            QuackError::invalid_argument("Either a window title or a selector is required")
+        })?;
+    selector.resolve(native())
+}
+
+/// Types or pastes `text` into the selected window. Returns the injection
+/// mode that was used (`"type"` or `"paste"`).
+///
+/// Fails with a `confirmation_required` error for targets the injection
+/// policy flags as risky; ask the user, then call again with the returned
+/// token as `options.confirmation`.
+///
+/// This and the other commands that type or wait for typing are async so
+/// they don't block the main thread, and with it every webview.
+#[tauri::command(async)]
+pub fn inject_text_to_window_by_title(
+    text: String,
+    window_title: Option<String>,
+    selector: Option<WindowSelector>,
+    options: Option<InjectionOptions>,
+) -> Result<InjectionMode, QuackError> {
+    let window = select_window(window_title, selector)?;
+    injection::inject(native(), window, &text, &options.unwrap_or_default())
+}
+
+/// Starts a streaming injection into the selected window. Feed it with
+/// `push_chunk` as tokens arrive and finish with `end_injection` or
+/// `cancel_injection`. Confirmation works as for
+/// `inject_text_to_window_by_title`.
+#[tauri::command]
+pub fn begin_injection(
+    window_title: Option<String>,
+    selector: Option<WindowSelector>,
+    confirmation: Option<u64>,
+) -> Result<(), QuackError> {
+    let mut session = INJECTION_SESSION.lock()?;
+    if session.is_some() {
+        return Err(QuackError::invalid_state(
+            "An injection is already in progress",
+        ));
+    }
+    let window = select_window(window_title, selector)?;
+    *session = Some(InjectionSession::begin(native(), window, confirmation)?);
+    Ok(())
+}
+
+/// Runs `f` on the current injection session.
+fn with_session<T>(
+    f: impl FnOnce(&InjectionSession) -> Result<T, QuackError>,
+) -> Result<T, QuackError> {
+    let session = INJECTION_SESSION.lock()?;
+    let session = session
+        .as_ref()
+        .ok_or_else(|| QuackError::invalid_state("No injection in progress"))?;
+    f(session)
+}
+
+/// Removes the current injection session so it can be finished.
+fn take_session() -> Result<InjectionSession, QuackError> {
+    INJECTION_SESSION
+        .lock()?
+        .take()
+        .ok_or_else(|| QuackError::invalid_state("No injection in progress"))
+}
+
+/// Queues a chunk of text for the current injection. Fails once typing has
+/// stopped on an error, e.g. because the target window was closed.
+#[tauri::command]
+pub fn push_chunk(text: String) -> Result<(), QuackError> {
+    with_session(|session| session.push(text))
+}
+
+#[tauri::command]
+pub fn pause_injection() -> Result<(), QuackError> {
+    with_session(|session| {
+        session.pause();
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn resume_injection() -> Result<(), QuackError> {
+    with_session(|session| {
+        session.resume();
+        Ok(())
+    })
+}
+
+/// Types the rest of the queue and returns what was typed.
+#[tauri::command(async)]
+pub fn end_injection() -> Result<InjectionSummary, QuackError> {
+    Ok(take_session()?.end())
 }
+
+/// Stops typing right away, dropping queued chunks.
+#[tauri::command(async)]
+pub fn cancel_injection() -> Result<InjectionSummary, QuackError> {
+    Ok(take_session()?.cancel())
+}
+
+/// Reverts the most recent injection if the window it went into still has
+/// focus, and returns what was undone.
+#[tauri::command(async)]
+pub fn undo_last_injection() -> Result<InjectionRecord, QuackError> {
+    history::undo_last(native())
+}
+
+/// Replaces the deny-, allow- and confirm-lists used for every injection.
 #[tauri::command]
-pub fn inject_text_to_window_by_title(text: String, window_title: String) -> Result<(), String> {
-	unsafe {
-		let wide_title: Vec<u16> = std::ffi::OsStr::new(&window_title)
-			.encode_wide()
-			.chain(std::iter::once(0))
-			.collect();
-
-		let hwnd = FindWindowW(std::ptr::null(), wide_title.as_ptr());
-		if hwnd.is_null() {
-			return Err(format!("Window with title '{}' not found", window_title));
-		}
-		inject_text_to_window(text, hwnd)
-	}
-}
-
-fn inject_text_to_window(text: String, hwnd: WinHWND) -> Result<(), String> {
-	unsafe {
-		if SetForegroundWindow(hwnd) == 0 {
-			return Err("Failed to bring target window to foreground".into());
-		}
-		std::thread::sleep(std::time::Duration::from_millis(50));
-		for ch in text.chars() {
-			let mut input = INPUT {
-				type_: INPUT_KEYBOARD,
-				u: std::mem::zeroed(),
-			};
-			*input.u.ki_mut() = KEYBDINPUT {
-				wVk: 0,
-				wScan: ch as u16,
-				dwFlags: KEYEVENTF_UNICODE,
-				time: 0,
-				dwExtraInfo: 0,
-			};
-			SendInput(1, &mut input, std::mem::size_of::<INPUT>() as i32);
-		}
-		Ok(())
-	}
+pub fn set_injection_policy(policy: InjectionPolicy) -> Result<(), QuackError> {
+    *injection::policy::policy().lock()? = policy;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_injection_policy() -> Result<InjectionPolicy, QuackError> {
+    Ok(injection::policy::policy().lock()?.clone())
+}
+
+/// Replaces the deny-list of windows blacked out in redacted captures.
+#[tauri::command]
+pub fn set_redaction_policy(policy: RedactionPolicy) -> Result<(), QuackError> {
+    *redact::policy().lock()? = policy;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_redaction_policy() -> Result<RedactionPolicy, QuackError> {
+    Ok(redact::policy().lock()?.clone())
 }
 
 #[derive(serde::Serialize)]
 pub struct WindowInfo {
-	pub hwnd: isize,
-	pub title: String,
+    pub hwnd: isize,
+    pub title: String,
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    /// Key to pass to `get_app_icon` to fetch the window icon.
+    pub icon_key: Option<String>,
+    pub bounds: WindowBounds,
+    /// Index of the monitor holding the window's center, as used by
+    /// `capture_screen`'s `monitor` target.
+    pub monitor: Option<usize>,
+    /// Position in the stacking order, 0 being the topmost window.
+    pub z_order: usize,
+    pub cloaked: bool,
+    pub tool_window: bool,
+}
+
+/// Filters for `list_windows`. Cloaked, tool and Quack's own windows are left
+/// out unless asked for.
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ListWindowsOptions {
+    pub include_cloaked: bool,
+    pub include_tool_windows: bool,
+    /// Include Quack's own overlay, chat and main windows.
+    pub include_own: bool,
+}
+
+/// Lists titled top-level windows, topmost first, e.g. for a "pick a window" menu.
+#[tauri::command]
+pub fn list_windows(options: Option<ListWindowsOptions>) -> Vec<WindowInfo> {
+    let options = options.unwrap_or_default();
+    let platform = native();
+    let monitors = platform.monitors().unwrap_or_default();
+    let own_pid = std::process::id();
+
+    platform
+        .list_windows()
+        .into_iter()
+        .filter(|w| options.include_cloaked || !w.cloaked)
+        .filter(|w| options.include_tool_windows || !w.tool_window)
+        .filter_map(|w| {
+            let pid = platform.window_pid(w.id);
+            if !options.include_own && pid == Some(own_pid) {
+                return None;
+            }
+            let title = platform.window_title(w.id);
+            if title.is_empty() {
+                return None;
+            }
+            Some((w, pid, title))
+        })
+        .enumerate()
+        .map(|(z_order, (w, pid, title))| {
+            let center_x = w.bounds.x + (w.bounds.width / 2) as i32;
+            let center_y = w.bounds.y + (w.bounds.height / 2) as i32;
+            let monitor = monitors
+                .iter()
+                .find(|m| {
+                    let b = m.bounds;
+                    center_x >= b.x
+                        && center_x < b.x + b.width as i32
+                        && center_y >= b.y
+                        && center_y < b.y + b.height as i32
+                })
+                .map(|m| m.index);
+            WindowInfo {
+                hwnd: w.id.as_raw(),
+                title,
+                pid,
+                process_name: platform
+                    .exe_path(w.id)
+                    .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned())),
+                icon_key: icon_cache::icon_key_for_window(platform, w.id),
+                bounds: w.bounds,
+                monitor,
+                z_order,
+                cloaked: w.cloaked,
+                tool_window: w.tool_window,
+            }
+        })
+        .collect()
 }
 
+/// Result of `capture_screen`, and payload of the `region_captured` event.
+#[derive(Clone, serde::Serialize)]
+pub struct ScreenCaptureResponse {
+    #[serde(flatten)]
+    pub image: CaptureOutput,
+    /// Captured area in physical pixels, positioned on the virtual desktop.
+    pub bounds: WindowBounds,
+    /// Name and scale factor of the captured monitor; `None` for the whole
+    /// virtual desktop.
+    pub monitor: Option<MonitorInfo>,
+}
+
+/// Captures the primary monitor.
 #[tauri::command]
-pub fn capture_window_screenshot() -> Result<String, String> {
-	unsafe {
-		// Get screen dimensions
-		let screen_width = GetSystemMetrics(SM_CXSCREEN);
-		let screen_height = GetSystemMetrics(SM_CYSCREEN);
-
-		if screen_width <= 0 || screen_height <= 0 {
-			return Err("Invalid screen dimensions".to_string());
-		}
-
-		// Get screen DC
-		let screen_dc = GetDC(std::ptr::null_mut());
-		if screen_dc.is_null() {
-			return Err("Failed to get screen DC".to_string());
-		}
-
-		// Create memory DC
-		let memory_dc = CreateCompatibleDC(screen_dc);
-		if memory_dc.is_null() {
-			ReleaseDC(std::ptr::null_mut(), screen_dc);
-			return Err("Failed to create memory DC".to_string());
-		}
-
-		// Create bitmap
-		let bitmap = CreateCompatibleBitmap(screen_dc, screen_width, screen_height);
-		if bitmap.is_null() {
-			DeleteDC(memory_dc);
-			ReleaseDC(std::ptr::null_mut(), screen_dc);
-			return Err("Failed to create bitmap".to_string());
-		}
-
-		let old_bitmap = SelectObject(memory_dc, bitmap as *mut c_void);
-
-		// Copy screen to bitmap
-		if BitBlt(memory_dc, 0, 0, screen_width, screen_height, screen_dc, 0, 0, SRCCOPY) == 0 {
-			SelectObject(memory_dc, old_bitmap);
-			DeleteObject(bitmap as *mut c_void);
-			DeleteDC(memory_dc);
-			ReleaseDC(std::ptr::null_mut(), screen_dc);
-			return Err("Failed to capture screen".to_string());
-		}
-
-		// Get bitmap data
-		let mut bitmap_info: BITMAPINFO = std::mem::zeroed();
-		bitmap_info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as u32;
-		bitmap_info.bmiHeader.biWidth = screen_width;
-		bitmap_info.bmiHeader.biHeight = -screen_height; // Negative for top-down
-		bitmap_info.bmiHeader.biPlanes = 1;
-		bitmap_info.bmiHeader.biBitCount = 32;
-		bitmap_info.bmiHeader.biCompression = BI_RGB;
-
-		let bitmap_size = (screen_width * screen_height * 4) as usize;
-		let mut buffer: Vec<u8> = vec![0; bitmap_size];
-
-		if GetDIBits(memory_dc, bitmap, 0, screen_height as u32, buffer.as_mut_ptr() as *mut c_void, &mut bitmap_info, DIB_RGB_COLORS) == 0 {
-			SelectObject(memory_dc, old_bitmap);
-			DeleteObject(bitmap as *mut c_void);
-			DeleteDC(memory_dc);
-			ReleaseDC(std::ptr::null_mut(), screen_dc);
-			return Err("Failed to get bitmap data".to_string());
-		}
-
-		// Convert BGRA to RGBA
-		for chunk in buffer.chunks_exact_mut(4) {
-			let b = chunk[0];
-			let g = chunk[1];
-			let r = chunk[2];
-			let a = chunk[3];
-			chunk[0] = r;
-			chunk[1] = g;
-			chunk[2] = b;
-			chunk[3] = a;
-		}
-
-		// Create image
-		let img = match image::RgbaImage::from_raw(screen_width as u32, screen_height as u32, buffer) {
-			Some(img) => img,
-			None => {
-				SelectObject(memory_dc, old_bitmap);
-				DeleteObject(bitmap as *mut c_void);
-				DeleteDC(memory_dc);
-				ReleaseDC(std::ptr::null_mut(), screen_dc);
-				return Err("Failed to create image".to_string());
-			}
-		};
-
-		// Convert to PNG
-		let dynamic_img = DynamicImage::ImageRgba8(img);
-		let mut png_data = Vec::new();
-		if dynamic_img.write_to(&mut io::Cursor::new(&mut png_data), ImageFormat::Png).is_err() {
-			SelectObject(memory_dc, old_bitmap);
-			DeleteObject(bitmap as *mut c_void);
-			DeleteDC(memory_dc);
-			ReleaseDC(std::ptr::null_mut(), screen_dc);
-			return Err("Failed to encode PNG".to_string());
-		}
-
-		// Cleanup
-		SelectObject(memory_dc, old_bitmap);
-		DeleteObject(bitmap as *mut c_void);
-		DeleteDC(memory_dc);
-		ReleaseDC(std::ptr::null_mut(), screen_dc);
-
-		let base64_data = encode(&png_data);
-		Ok(format!("data:image/png;base64,{}", base64_data))
-	}
-}
-
-fn capture_hwnd_to_png_base64(hwnd: WinHWND) -> Result<String, String> {
-	unsafe {
-		let mut window_rect: RECT = std::mem::zeroed();
-		if GetWindowRect(hwnd, &mut window_rect) == 0 {
-			return Err("Failed to get window rectangle".to_string());
-		}
-
-		let width = (window_rect.right - window_rect.left).max(0);
-		let height = (window_rect.bottom - window_rect.top).max(0);
-		if width == 0 || height == 0 {
-			return Err("Invalid window dimensions".to_string());
-		}
-
-		let window_dc = GetDC(hwnd);
-		if window_dc.is_null() {
-			return Err("Failed to get window DC".to_string());
-		}
-
-		let memory_dc = CreateCompatibleDC(window_dc);
-		if memory_dc.is_null() {
-			ReleaseDC(hwnd, window_dc);
-			return Err("Failed to create memory DC".to_string());
-		}
-
-		let bitmap = CreateCompatibleBitmap(window_dc, width, height);
-		if bitmap.is_null() {
-			DeleteDC(memory_dc);
-			ReleaseDC(hwnd, window_dc);
-			return Err("Failed to create compatible bitmap".to_string());
-		}
-
-		let old_bitmap = SelectObject(memory_dc, bitmap as *mut c_void);
-
-		// Try PrintWindow with full-content first (more robust for occluded/UWP windows)
-		let mut pw_ok = PrintWindow(hwnd, memory_dc, 2);
-		if pw_ok == 0 {
-			// Fallback to default flags
-			pw_ok = PrintWindow(hwnd, memory_dc, 0);
-		}
-		if pw_ok == 0 {
-			// Fallback to BitBlt from window DC
-			if BitBlt(memory_dc, 0, 0, width, height, window_dc, 0, 0, SRCCOPY) == 0 {
-				SelectObject(memory_dc, old_bitmap);
-				DeleteObject(bitmap as *mut c_void);
-				DeleteDC(memory_dc);
-				ReleaseDC(hwnd, window_dc);
-				return Err("Failed to capture window content".to_string());
-			}
-		}
-
-		// Prepare to extract bitmap bits
-		let mut bitmap_info: BITMAPINFO = std::mem::zeroed();
-		bitmap_info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as u32;
-		bitmap_info.bmiHeader.biWidth = width;
-		bitmap_info.bmiHeader.biHeight = -height; // top-down
-		bitmap_info.bmiHeader.biPlanes = 1;
-		bitmap_info.bmiHeader.biBitCount = 32; // BGRA
-		bitmap_info.bmiHeader.biCompression = BI_RGB;
-
-		let bitmap_size = (width * height * 4) as usize;
-		let mut buffer: Vec<u8> = vec![0; bitmap_size];
-		if GetDIBits(
-			memory_dc,
-			bitmap,
-			0,
-			height as u32,
-			buffer.as_mut_ptr() as *mut c_void,
-			&mut bitmap_info,
-			DIB_RGB_COLORS,
-		) == 0
-		{
-			SelectObject(memory_dc, old_bitmap);
-			DeleteObject(bitmap as *mut c_void);
-			DeleteDC(memory_dc);
-			ReleaseDC(hwnd, window_dc);
-			return Err("Failed to get bitmap data".to_string());
-		}
-
-		// Convert BGRA -> RGBA
-		for chunk in buffer.chunks_exact_mut(4) {
-			let b = chunk[0];
-			let g = chunk[1];
-			let r = chunk[2];
-			let a = chunk[3];
-			chunk[0] = r;
-			chunk[1] = g;
-			chunk[2] = b;
-			chunk[3] = a;
-		}
-
-		let img = match image::RgbaImage::from_raw(width as u32, height as u32, buffer) {
-			Some(img) => img,
-			None => {
-				SelectObject(memory_dc, old_bitmap);
-				DeleteObject(bitmap as *mut c_void);
-				DeleteDC(memory_dc);
-				ReleaseDC(hwnd, window_dc);
-				return Err("Failed to create image".to_string());
-			}
-		};
-
-		let dynamic_img = DynamicImage::ImageRgba8(img);
-		let mut png_data = Vec::new();
-		if dynamic_img
-			.write_to(&mut io::Cursor::new(&mut png_data), ImageFormat::Png)
-			.is_err()
-		{
-			SelectObject(memory_dc, old_bitmap);
-			DeleteObject(bitmap as *mut c_void);
-			DeleteDC(memory_dc);
-			ReleaseDC(hwnd, window_dc);
-			return Err("Failed to encode PNG".to_string());
-		}
-
-		// Cleanup
-		SelectObject(memory_dc, old_bitmap);
-		DeleteObject(bitmap as *mut c_void);
-		DeleteDC(memory_dc);
-		ReleaseDC(hwnd, window_dc);
-
-		let base64_data = encode(&png_data);
-		Ok(format!("data:image/png;base64,{}", base64_data))
-	}
+pub fn capture_window_screenshot(
+    options: Option<CaptureOptions>,
+) -> Result<CaptureOutput, QuackError> {
+    let screen = native().capture_screen(ScreenTarget::Primary)?;
+    let area = CaptureArea::Screen(screen.bounds);
+    capture::encode(screen.image, area, &options.unwrap_or_default())
 }
 
+/// Captures a monitor, or the whole virtual desktop, in physical pixels.
+/// Defaults to the primary monitor.
 #[tauri::command]
-pub fn capture_window_screenshot_by_title(window_title: String) -> Result<String, String> {
-	unsafe {
-		let wide_title: Vec<u16> = std::ffi::OsStr::new(&window_title)
-			.encode_wide()
-			.chain(std::iter::once(0))
-			.collect();
+pub fn capture_screen(
+    target: Option<ScreenTarget>,
+    options: Option<CaptureOptions>,
+) -> Result<ScreenCaptureResponse, QuackError> {
+    let screen = native().capture_screen(target.unwrap_or_default())?;
+    Ok(ScreenCaptureResponse {
+        image: capture::encode(
+            screen.image,
+            CaptureArea::Screen(screen.bounds),
+            &options.unwrap_or_default(),
+        )?,
+        bounds: screen.bounds,
+        monitor: screen.monitor,
+    })
+}
 
-		let hwnd = FindWindowW(std::ptr::null(), wide_title.as_ptr());
-		if hwnd.is_null() {
-			return Err(format!("Window '{}' not found", window_title));
-		}
+#[tauri::command]
+pub fn capture_window_screenshot_by_title(
+    window_title: Option<String>,
+    selector: Option<WindowSelector>,
+    options: Option<CaptureOptions>,
+) -> Result<CaptureOutput, QuackError> {
+    let window = select_window(window_title, selector)?;
+    let image = native().capture_window(window)?;
+    capture::encode(
+        image,
+        CaptureArea::Window(window),
+        &options.unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+pub fn capture_window_screenshot_by_hwnd(
+    hwnd: isize,
+    options: Option<CaptureOptions>,
+) -> Result<CaptureOutput, QuackError> {
+    let window =
+        WindowId::from_raw(hwnd).ok_or_else(|| QuackError::invalid_argument("Invalid HWND"))?;
+    let image = native().capture_window(window)?;
+    capture::encode(
+        image,
+        CaptureArea::Window(window),
+        &options.unwrap_or_default(),
+    )
+}
+
+/// Captures a rectangle of the virtual desktop, given in physical pixels.
+#[tauri::command]
+pub fn capture_region(
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+    options: Option<CaptureOptions>,
+) -> Result<CaptureOutput, QuackError> {
+    if w == 0 || h == 0 {
+        return Err(QuackError::invalid_argument("The region is empty"));
+    }
+    let bounds = WindowBounds {
+        x,
+        y,
+        width: w,
+        height: h,
+    };
+    let image = native().capture_region(bounds)?;
+    capture::encode(
+        image,
+        CaptureArea::Screen(bounds),
+        &options.unwrap_or_default(),
+    )
+}
+
+/// A rectangle dragged out in a selection window, in CSS pixels.
+#[derive(serde::Deserialize)]
+pub struct SelectionRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+fn close_region_selectors(app: &AppHandle) {
+    for (label, window) in app.webview_windows() {
+        if label.starts_with(REGION_SELECTOR_LABEL) {
+            let _ = window.close();
+        }
+    }
+}
 
-		capture_hwnd_to_png_base64(hwnd)
-	}
+/// Freezes every monitor: each is captured and covered with a selection window
+/// (the `/region-selector` route) that shows the captured frame, so what the
+/// user selects is what gets cropped. The user drags a rectangle on one of
+/// them, which reports it through `finish_region_selection`.
+///
+/// Async so the windows aren't created on the main thread, which deadlocks on
+/// Windows.
+#[tauri::command(async)]
+pub fn start_region_selection(
+    app: AppHandle,
+    options: Option<CaptureOptions>,
+) -> Result<(), QuackError> {
+    let platform = native();
+    let monitors = platform.monitors()?;
+    let captures = monitors
+        .iter()
+        .map(|m| platform.capture_screen(ScreenTarget::Monitor(m.index)))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Only a backdrop, so the quicker JPEG will do.
+    let backdrop = CaptureOptions {
+        format: OutputFormat::Jpeg,
+        ..Default::default()
+    };
+    let frames = captures
+        .iter()
+        .map(|c| capture::encode(c.image.clone(), CaptureArea::Screen(c.bounds), &backdrop))
+        .collect::<Result<Vec<_>, _>>()?;
+    *REGION_SELECTION.lock()? = Some((captures, options.unwrap_or_default()));
+
+    close_region_selectors(&app);
+    for (monitor, frame) in monitors.iter().zip(&frames) {
+        let label = format!("{}{}", REGION_SELECTOR_LABEL, monitor.index);
+        let url = format!(
+            "/region-selector?monitor={}&frame={}",
+            monitor.index, frame.url
+        );
+        let window = WebviewWindowBuilder::new(&app, label, WebviewUrl::App(url.into()))
+            .title("region-selector")
+            .transparent(true)
+            .decorations(false)
+            .resizable(false)
+            .shadow(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .visible(false)
+            .build()
+            .map_err(|e| {
+                close_region_selectors(&app);
+                QuackError::os(e)
+            })?;
+        // Physical units, since monitors may use different scale factors.
+        let bounds = monitor.bounds;
+        let _ = window.set_position(tauri::PhysicalPosition::new(bounds.x, bounds.y));
+        let _ = window.set_size(tauri::PhysicalSize::new(bounds.width, bounds.height));
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    Ok(())
 }
 
+/// Completes `start_region_selection` with the rectangle the user dragged on
+/// monitor `monitor`, or cancels it when `selection` is `None`.
+///
+/// Closes the selection windows and crops the frozen capture of that monitor.
+/// The result is returned and also emitted as `region_captured`; a cancelled
+/// selection emits `region_selection_cancelled` instead.
 #[tauri::command]
-pub fn capture_window_screenshot_by_hwnd(hwnd: isize) -> Result<String, String> {
-	unsafe {
-		let hwnd_ptr = hwnd as WinHWND;
-		if hwnd_ptr.is_null() {
-			return Err("Invalid HWND".to_string());
-		}
-		capture_hwnd_to_png_base64(hwnd_ptr)
-	}
+pub fn finish_region_selection(
+    app: AppHandle,
+    monitor: usize,
+    selection: Option<SelectionRect>,
+) -> Result<Option<ScreenCaptureResponse>, QuackError> {
+    let (captures, options) = REGION_SELECTION
+        .lock()?
+        .take()
+        .ok_or_else(|| QuackError::invalid_state("No region selection is in progress"))?;
+    let scale = app
+        .get_webview_window(&format!("{}{}", REGION_SELECTOR_LABEL, monitor))
+        .and_then(|w| w.scale_factor().ok())
+        .unwrap_or(1.0);
+    close_region_selectors(&app);
+
+    let Some(selection) = selection else {
+        let _ = app.emit("region_selection_cancelled", ());
+        return Ok(None);
+    };
+    let screen = captures
+        .into_iter()
+        .nth(monitor)
+        .ok_or_else(|| QuackError::invalid_argument(format!("No monitor at index {}", monitor)))?;
+
+    // CSS pixels to physical pixels, clamped to the monitor.
+    let (max_width, max_height) = screen.image.dimensions();
+    let to_physical = |v: f64, max: u32| ((v * scale).round().max(0.0) as u32).min(max);
+    let x = to_physical(selection.x, max_width);
+    let y = to_physical(selection.y, max_height);
+    let width = to_physical(selection.width, max_width - x);
+    let height = to_physical(selection.height, max_height - y);
+    if width == 0 || height == 0 {
+        return Err(QuackError::invalid_argument("The region is empty"));
+    }
+
+    let image = image::imageops::crop_imm(&screen.image, x, y, width, height).to_image();
+    let bounds = WindowBounds {
+        x: screen.bounds.x + x as i32,
+        y: screen.bounds.y + y as i32,
+        width,
+        height,
+    };
+    let response = ScreenCaptureResponse {
+        image: capture::encode(image, CaptureArea::Screen(bounds), &options)?,
+        bounds,
+        monitor: screen.monitor,
+    };
+    let _ = app.emit("region_captured", response.clone());
+    Ok(Some(response))
 }
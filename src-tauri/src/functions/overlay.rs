@@ -1,5 +1,4 @@
 use crate::utils::{smooth_move, smooth_resize};
-use enigo::{Enigo, MouseControllable};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
 
@@ -208,7 +207,6 @@ pub struct NotchWatcher;
 impl NotchWatcher {
     pub fn start(window: tauri::WebviewWindow) {
         std::thread::spawn(move || {
-            let enigo = Enigo::new();
             // Try to get the screen width from the window's monitor
             let screen_width = window
                 .current_monitor()
@@ -219,7 +217,11 @@ impl NotchWatcher {
             let notch_x = (screen_width - NOTCH_WIDTH) / 2;
             let notch_y = 0;
             loop {
-                let (x, y) = enigo.mouse_location();
+                // Physical pixels, like the monitor width above
+                let (x, y) = window
+                    .cursor_position()
+                    .map(|p| (p.x as i32, p.y as i32))
+                    .unwrap_or((-1, -1));
                 // Check if mouse is inside the centered notch area
                 if x >= notch_x
                     && x < notch_x + NOTCH_WIDTH
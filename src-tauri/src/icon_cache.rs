@@ -0,0 +1,216 @@
+//! In-memory LRU cache of application icons, keyed by executable path or
+//! AppUserModel ID (see [`Platform::icon_key`]).
+//!
+//! Resolving an icon walks several OS APIs and PNG-encodes the result, so the
+//! window watcher only does it the first time it sees an application. Events
+//! then carry the key, and the frontend fetches the icon once via `get_app_icon`.
+//! When a disk directory is configured, icons are also written there as PNG
+//! files so they survive restarts.
+
+use crate::platform::{Platform, WindowId};
+use base64::{engine::general_purpose, Engine as _};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// Number of icons kept in memory.
+const DEFAULT_CAPACITY: usize = 128;
+
+pub struct IconCache {
+    capacity: usize,
+    /// Base64 PNG per key.
+    entries: HashMap<String, String>,
+    /// Keys from least to most recently used.
+    order: VecDeque<String>,
+    disk_dir: Option<PathBuf>,
+}
+
+impl IconCache {
+    pub fn new(capacity: usize) -> Self {
+        IconCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            disk_dir: None,
+        }
+    }
+
+    /// Mirrors icons to `dir`, or stops doing so when `None`.
+    pub fn set_disk_dir(&mut self, dir: Option<PathBuf>) {
+        if let Some(dir) = &dir {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        self.disk_dir = dir;
+    }
+
+    pub fn disk_dir(&self) -> Option<&PathBuf> {
+        self.disk_dir.as_ref()
+    }
+
+    /// Looks an icon up in memory, then on disk. Marks it as most recently used.
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        if let Some(icon) = self.entries.get(key).cloned() {
+            self.touch(key);
+            return Some(icon);
+        }
+        let bytes = std::fs::read(self.disk_path(key)?).ok()?;
+        let icon = general_purpose::STANDARD.encode(bytes);
+        self.insert_in_memory(key.to_string(), icon.clone());
+        Some(icon)
+    }
+
+    /// Stores a Base64 PNG icon, evicting the least recently used entry if full.
+    pub fn insert(&mut self, key: String, icon_base64: String) {
+        if let Some(path) = self.disk_path(&key) {
+            if let Ok(bytes) = general_purpose::STANDARD.decode(&icon_base64) {
+                let _ = std::fs::write(path, bytes);
+            }
+        }
+        self.insert_in_memory(key, icon_base64);
+    }
+
+    fn insert_in_memory(&mut self, key: String, icon_base64: String) {
+        if self.entries.insert(key.clone(), icon_base64).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            if let Some(k) = self.order.remove(pos) {
+                self.order.push_back(k);
+            }
+        }
+    }
+
+    /// File name is a hash of the key; keys are paths and may contain any character.
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        // FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`.
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        Some(self.disk_dir.as_ref()?.join(format!("{:016x}.png", hash)))
+    }
+}
+
+/// The process-wide icon cache.
+pub fn cache() -> &'static Mutex<IconCache> {
+    static CACHE: OnceLock<Mutex<IconCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(IconCache::new(DEFAULT_CAPACITY)))
+}
+
+/// Returns the icon key for `window`, resolving and caching its icon the first
+/// time the key is seen. Returns `None` when the window has no usable icon.
+pub fn icon_key_for_window(platform: &impl Platform, window: WindowId) -> Option<String> {
+    let key = platform.icon_key(window)?;
+    if cache().lock().ok()?.get(&key).is_some() {
+        return Some(key);
+    }
+    // Resolve outside the lock; icon lookups can take a while.
+    let icon = platform.window_icon_base64(window)?;
+    cache().lock().ok()?.insert(key.clone(), icon);
+    Some(key)
+}
+
+/// Looks up a cached icon by key.
+pub fn get_icon(key: &str) -> Option<String> {
+    cache().lock().ok()?.get(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Step {
+        Insert(&'static str),
+        Get(&'static str),
+    }
+    use Step::{Get, Insert};
+
+    /// Runs `steps` against an in-memory cache; each icon is its key.
+    fn run(capacity: usize, steps: &[Step]) -> IconCache {
+        let mut cache = IconCache::new(capacity);
+        for step in steps {
+            match *step {
+                Insert(key) => cache.insert(key.to_string(), key.to_string()),
+                Get(key) => {
+                    cache.get(key);
+                }
+            }
+        }
+        cache
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        // (capacity, steps, keys kept from least to most recently used)
+        let cases: [(usize, &[Step], &[&str]); 6] = [
+            (2, &[Insert("a"), Insert("b"), Insert("c")], &["b", "c"]),
+            // A hit makes the entry the most recently used.
+            (
+                2,
+                &[Insert("a"), Insert("b"), Get("a"), Insert("c")],
+                &["a", "c"],
+            ),
+            // So does inserting it again.
+            (
+                2,
+                &[Insert("a"), Insert("b"), Insert("a"), Insert("c")],
+                &["a", "c"],
+            ),
+            // A miss changes nothing.
+            (2, &[Insert("a"), Insert("b"), Get("x")], &["a", "b"]),
+            (
+                3,
+                &[Insert("a"), Insert("b"), Insert("c"), Get("b"), Get("a")],
+                &["c", "b", "a"],
+            ),
+            // Capacity is at least one.
+            (0, &[Insert("a"), Insert("b")], &["b"]),
+        ];
+        for (capacity, steps, expected) in cases {
+            let cache = run(capacity, steps);
+            assert_eq!(cache.order, expected);
+            let mut keys: Vec<&str> = cache.entries.keys().map(String::as_str).collect();
+            keys.sort_unstable();
+            let mut expected = expected.to_vec();
+            expected.sort_unstable();
+            assert_eq!(keys, expected);
+        }
+    }
+
+    #[test]
+    fn never_holds_more_than_its_capacity() {
+        let mut cache = IconCache::new(3);
+        for i in 0..20 {
+            cache.insert(format!("key{}", i), "icon".to_string());
+            assert!(cache.entries.len() <= 3 && cache.order.len() == cache.entries.len());
+        }
+        assert_eq!(cache.order, ["key17", "key18", "key19"]);
+    }
+
+    #[test]
+    fn reloads_evicted_icons_from_disk() {
+        let dir = std::env::temp_dir().join(format!("quack-icons-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut cache = IconCache::new(1);
+        cache.set_disk_dir(Some(dir.clone()));
+        let icon = general_purpose::STANDARD.encode(b"not really a png");
+        cache.insert("/usr/bin/app".to_string(), icon.clone());
+        cache.insert("/usr/bin/other".to_string(), icon.clone());
+        assert!(!cache.entries.contains_key("/usr/bin/app"));
+
+        assert_eq!(cache.get("/usr/bin/app"), Some(icon));
+        assert_eq!(cache.order, ["/usr/bin/app"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
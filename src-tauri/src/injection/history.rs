@@ -0,0 +1,107 @@
+//! Recently injected text, so the last injection can be undone.
+
+use super::{
+    plan::{plan_backspaces, plan_chord, typed_len, NamedKey},
+    InjectionMode,
+};
+use crate::error::QuackError;
+use crate::platform::{Platform, WindowId};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of injections remembered.
+const HISTORY_CAPACITY: usize = 20;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct InjectionRecord {
+    #[serde(rename = "hwnd")]
+    pub window: WindowId,
+    pub text: String,
+    /// The mode that was actually used, never [`InjectionMode::Auto`].
+    pub mode: InjectionMode,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl InjectionRecord {
+    pub fn new(window: WindowId, text: String, mode: InjectionMode) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        InjectionRecord {
+            window,
+            text,
+            mode,
+            timestamp,
+        }
+    }
+}
+
+/// Bounded list of injections, oldest first.
+pub struct InjectionHistory {
+    records: VecDeque<InjectionRecord>,
+}
+
+impl InjectionHistory {
+    pub fn push(&mut self, record: InjectionRecord) {
+        if record.text.is_empty() {
+            return;
+        }
+        self.records.push_back(record);
+        while self.records.len() > HISTORY_CAPACITY {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn last(&self) -> Option<&InjectionRecord> {
+        self.records.back()
+    }
+
+    pub fn pop(&mut self) -> Option<InjectionRecord> {
+        self.records.pop_back()
+    }
+}
+
+/// The process-wide injection history.
+pub fn history() -> &'static Mutex<InjectionHistory> {
+    static HISTORY: OnceLock<Mutex<InjectionHistory>> = OnceLock::new();
+    HISTORY.get_or_init(|| {
+        Mutex::new(InjectionHistory {
+            records: VecDeque::new(),
+        })
+    })
+}
+
+/// Adds an injection to the history.
+pub fn record(window: WindowId, text: String, mode: InjectionMode) {
+    if let Ok(mut history) = history().lock() {
+        history.push(InjectionRecord::new(window, text, mode));
+    }
+}
+
+/// Reverts the most recent injection and removes it from the history.
+///
+/// Typed text is removed with Backspace, pasted text with the target's own
+/// undo (Ctrl+Z). Nothing is sent unless the window the text went into still
+/// has focus, since the keys would otherwise land somewhere else.
+pub fn undo_last(platform: &impl Platform) -> Result<InjectionRecord, QuackError> {
+    let mut history = history().lock()?;
+    let record = history
+        .last()
+        .ok_or_else(|| QuackError::invalid_state("There is no injection to undo"))?;
+    if platform.foreground_window() != Some(record.window) {
+        return Err(QuackError::invalid_state(
+            "The window the text was injected into no longer has focus",
+        ));
+    }
+    let events = match record.mode {
+        InjectionMode::Paste => plan_chord(NamedKey::Control, NamedKey::Letter('Z')),
+        _ => plan_backspaces(typed_len(&record.text)),
+    };
+    platform.send_input(&events)?;
+    Ok(history.pop().expect("checked above"))
+}
@@ -0,0 +1,106 @@
+//! Typing text into other applications.
+//!
+//! The platform backends only know how to send low-level key events and
+//! swap clipboard contents; this module decides which to use and what to send.
+
+use crate::error::QuackError;
+use crate::platform::{Platform, WindowId};
+
+pub mod history;
+pub mod plan;
+pub mod policy;
+pub mod session;
+
+/// Text longer than this many characters is pasted rather than typed in
+/// [`InjectionMode::Auto`]. Typing is reliable but slow for long answers.
+pub const AUTO_PASTE_THRESHOLD: usize = 200;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionMode {
+    /// Synthesize a keystroke per character.
+    Type,
+    /// Put the text on the clipboard, send the paste shortcut, then restore
+    /// whatever the user had on the clipboard before.
+    Paste,
+    /// Paste long text, type short text.
+    #[default]
+    Auto,
+}
+
+/// What to put on the clipboard in paste mode. Targets that understand HTML
+/// or RTF pick those up instead of the plain text.
+pub struct PasteContent<'a> {
+    pub text: &'a str,
+    pub html: Option<&'a str>,
+    pub rtf: Option<&'a str>,
+}
+
+/// Options accepted by the injection commands.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct InjectionOptions {
+    pub mode: InjectionMode,
+    /// HTML version of the text, used in paste mode.
+    pub html: Option<String>,
+    /// RTF version of the text, used in paste mode.
+    pub rtf: Option<String>,
+    /// Token from a `confirmation_required` error the user has approved.
+    pub confirmation: Option<u64>,
+}
+
+/// Injects `text` into `window` and returns the mode that was actually used.
+/// The target is checked against the injection [`policy`] first, and
+/// successful injections are recorded in the [`history`] so they can be undone.
+///
+/// In `Auto` mode, a paste that couldn't get as far as sending the paste
+/// shortcut (a backend without clipboard support, or a clipboard held by
+/// another app) falls back to typing.
+pub fn inject(
+    platform: &impl Platform,
+    window: WindowId,
+    text: &str,
+    options: &InjectionOptions,
+) -> Result<InjectionMode, QuackError> {
+    policy::authorize(platform, window, options.confirmation)?;
+    let mode = inject_with_mode(platform, window, text, options)?;
+    history::record(window, text.to_string(), mode);
+    Ok(mode)
+}
+
+fn inject_with_mode(
+    platform: &impl Platform,
+    window: WindowId,
+    text: &str,
+    options: &InjectionOptions,
+) -> Result<InjectionMode, QuackError> {
+    let content = PasteContent {
+        text,
+        html: options.html.as_deref(),
+        rtf: options.rtf.as_deref(),
+    };
+    match options.mode {
+        InjectionMode::Type => platform
+            .inject_text(window, text)
+            .map(|_| InjectionMode::Type),
+        InjectionMode::Paste => platform
+            .paste_text(window, &content)
+            .map(|_| InjectionMode::Paste),
+        InjectionMode::Auto if text.chars().count() > AUTO_PASTE_THRESHOLD => {
+            match platform.paste_text(window, &content) {
+                Ok(()) => Ok(InjectionMode::Paste),
+                Err(QuackError::Unsupported { .. } | QuackError::ClipboardFailed { .. }) => {
+                    platform
+                        .inject_text(window, text)
+                        .map(|_| InjectionMode::Type)
+                }
+                // Anything else may have pasted already; typing too would
+                // insert the text twice.
+                Err(e) => Err(e),
+            }
+        }
+        InjectionMode::Auto => platform
+            .inject_text(window, text)
+            .map(|_| InjectionMode::Type),
+    }
+}
@@ -0,0 +1,315 @@
+//! Turns text into a sequence of keyboard events, independent of any OS API.
+//!
+//! Characters are sent as UTF-16 code units so the target receives them
+//! regardless of keyboard layout. Characters outside the Basic Multilingual
+//! Plane become a surrogate pair. Line breaks and tabs are sent as real
+//! Enter and Tab keystrokes, because many editors ignore a raw `'\n'` char.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Keys sent as keystrokes rather than as characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NamedKey {
+    Enter,
+    Tab,
+    Backspace,
+    Control,
+    /// A letter key, for shortcuts like Ctrl+V. Always an ASCII letter.
+    Letter(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyStroke {
+    /// A single UTF-16 code unit (possibly one half of a surrogate pair).
+    Unicode(u16),
+    Named(NamedKey),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub stroke: KeyStroke,
+    /// `true` for key down, `false` for key up.
+    pub down: bool,
+}
+
+impl InputEvent {
+    fn press(stroke: KeyStroke) -> [InputEvent; 2] {
+        [
+            InputEvent { stroke, down: true },
+            InputEvent {
+                stroke,
+                down: false,
+            },
+        ]
+    }
+}
+
+/// Plans the key events that type `text`.
+///
+/// Every key down is paired with a key up. `"\r\n"`, `'\r'` and `'\n'` all
+/// become a single Enter; other control characters are dropped.
+pub fn plan_text(text: &str) -> Vec<InputEvent> {
+    let mut events = Vec::with_capacity(text.len() * 2);
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                events.extend(InputEvent::press(KeyStroke::Named(NamedKey::Enter)));
+            }
+            '\n' => events.extend(InputEvent::press(KeyStroke::Named(NamedKey::Enter))),
+            '\t' => events.extend(InputEvent::press(KeyStroke::Named(NamedKey::Tab))),
+            c if c.is_control() => {}
+            c => {
+                let mut units = [0u16; 2];
+                match *c.encode_utf16(&mut units) {
+                    [unit] => events.extend(InputEvent::press(KeyStroke::Unicode(unit))),
+                    [high, low] => {
+                        // Both halves go down before either comes up, so the
+                        // target sees the two character messages back to back.
+                        let (high, low) = (KeyStroke::Unicode(high), KeyStroke::Unicode(low));
+                        events.extend([
+                            InputEvent {
+                                stroke: high,
+                                down: true,
+                            },
+                            InputEvent {
+                                stroke: low,
+                                down: true,
+                            },
+                            InputEvent {
+                                stroke: low,
+                                down: false,
+                            },
+                            InputEvent {
+                                stroke: high,
+                                down: false,
+                            },
+                        ]);
+                    }
+                    _ => unreachable!("a char is one or two UTF-16 code units"),
+                }
+            }
+        }
+    }
+    events
+}
+
+/// Number of Backspaces that remove `text` again once it's typed. Editors
+/// delete a whole grapheme cluster per Backspace (an emoji with a skin tone,
+/// a letter with combining accents), and a line break counts as one.
+/// Dropped control characters don't count.
+pub fn typed_len(text: &str) -> usize {
+    text.graphemes(true)
+        .filter(|g| {
+            !g.chars()
+                .all(|c| c.is_control() && !matches!(c, '\r' | '\n' | '\t'))
+        })
+        .count()
+}
+
+/// Plans `count` presses of Backspace.
+pub fn plan_backspaces(count: usize) -> Vec<InputEvent> {
+    (0..count)
+        .flat_map(|_| InputEvent::press(KeyStroke::Named(NamedKey::Backspace)))
+        .collect()
+}
+
+/// Plans a shortcut such as Ctrl+V: modifier down, key press, modifier up.
+pub fn plan_chord(modifier: NamedKey, key: NamedKey) -> Vec<InputEvent> {
+    let (modifier, key) = (KeyStroke::Named(modifier), KeyStroke::Named(key));
+    vec![
+        InputEvent {
+            stroke: modifier,
+            down: true,
+        },
+        InputEvent {
+            stroke: key,
+            down: true,
+        },
+        InputEvent {
+            stroke: key,
+            down: false,
+        },
+        InputEvent {
+            stroke: modifier,
+            down: false,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unicode(unit: u16) -> KeyStroke {
+        KeyStroke::Unicode(unit)
+    }
+
+    fn named(key: NamedKey) -> KeyStroke {
+        KeyStroke::Named(key)
+    }
+
+    /// Key-down strokes only, for cases where pairing is checked separately.
+    fn downs(events: &[InputEvent]) -> Vec<KeyStroke> {
+        events.iter().filter(|e| e.down).map(|e| e.stroke).collect()
+    }
+
+    /// Every key that goes down comes up again, and nothing comes up twice.
+    fn assert_balanced(events: &[InputEvent]) {
+        let mut held = Vec::new();
+        for event in events {
+            if event.down {
+                held.push(event.stroke);
+            } else {
+                let pos = held.iter().rposition(|&s| s == event.stroke);
+                held.remove(pos.expect("key up without key down"));
+            }
+        }
+        assert!(held.is_empty(), "keys left down: {:?}", held);
+    }
+
+    #[test]
+    fn plans_bmp_characters_as_single_units() {
+        let events = plan_text("aé€");
+        assert_eq!(
+            events,
+            [
+                InputEvent::press(unicode('a' as u16)),
+                InputEvent::press(unicode(0xE9)),
+                InputEvent::press(unicode(0x20AC)),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn plans_surrogate_pairs_down_down_up_up() {
+        // U+1F600 is D83D DE00 in UTF-16.
+        let (high, low) = (unicode(0xD83D), unicode(0xDE00));
+        assert_eq!(
+            plan_text("😀"),
+            [
+                InputEvent {
+                    stroke: high,
+                    down: true
+                },
+                InputEvent {
+                    stroke: low,
+                    down: true
+                },
+                InputEvent {
+                    stroke: low,
+                    down: false
+                },
+                InputEvent {
+                    stroke: high,
+                    down: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plans_line_breaks_and_tabs_as_keys() {
+        let enter = named(NamedKey::Enter);
+        let tab = named(NamedKey::Tab);
+        let (a, b) = (unicode('a' as u16), unicode('b' as u16));
+        let cases: [(&str, Vec<KeyStroke>); 6] = [
+            ("a\r\nb", vec![a, enter, b]),
+            ("a\rb", vec![a, enter, b]),
+            ("a\nb", vec![a, enter, b]),
+            ("a\n\rb", vec![a, enter, enter, b]),
+            ("a\r\n\r\nb", vec![a, enter, enter, b]),
+            ("a\tb", vec![a, tab, b]),
+        ];
+        for (text, want) in cases {
+            let events = plan_text(text);
+            assert_eq!(downs(&events), want, "{:?}", text);
+            assert_balanced(&events);
+        }
+    }
+
+    #[test]
+    fn drops_other_control_characters() {
+        let events = plan_text("a\u{0}\u{7}\u{1b}[0m\u{7f}\u{85}b");
+        let want: Vec<KeyStroke> = "a[0mb".chars().map(|c| unicode(c as u16)).collect();
+        assert_eq!(downs(&events), want);
+        assert_balanced(&events);
+    }
+
+    #[test]
+    fn plans_nothing_for_empty_text() {
+        assert!(plan_text("").is_empty());
+        assert!(plan_text("\u{0}\u{7}").is_empty());
+    }
+
+    #[test]
+    fn counts_what_the_target_receives() {
+        let cases = [
+            ("", 0),
+            ("abc", 3),
+            ("é€", 2),
+            ("😀", 1),
+            ("a\r\nb", 3),
+            ("a\rb", 3),
+            ("a\n\nb", 4),
+            ("a\tb", 3),
+            ("a\u{0}\u{7}b", 2),
+            ("e\u{301}", 1),
+            ("👍🏽", 1),
+            ("👨\u{200d}👩\u{200d}👧", 1),
+            ("🇯🇵!", 2),
+        ];
+        for (text, want) in cases {
+            assert_eq!(typed_len(text), want, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn typed_len_matches_planned_presses() {
+        for text in ["hello\r\nworld\t😀", "a\rb\u{1b}c", ""] {
+            let presses = plan_text(text).iter().filter(|e| e.down).count();
+            let surrogate_pairs = text.chars().filter(|c| c.len_utf16() == 2).count();
+            assert_eq!(typed_len(text), presses - surrogate_pairs, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn plans_backspaces() {
+        assert!(plan_backspaces(0).is_empty());
+        let events = plan_backspaces(3);
+        assert_eq!(events.len(), 6);
+        assert_eq!(downs(&events), [named(NamedKey::Backspace); 3]);
+        assert_eq!(
+            events,
+            InputEvent::press(named(NamedKey::Backspace)).repeat(3)
+        );
+    }
+
+    #[test]
+    fn plans_chords_modifier_outermost() {
+        let (ctrl, v) = (named(NamedKey::Control), named(NamedKey::Letter('V')));
+        assert_eq!(
+            plan_chord(NamedKey::Control, NamedKey::Letter('V')),
+            [
+                InputEvent {
+                    stroke: ctrl,
+                    down: true
+                },
+                InputEvent {
+                    stroke: v,
+                    down: true
+                },
+                InputEvent {
+                    stroke: v,
+                    down: false
+                },
+                InputEvent {
+                    stroke: ctrl,
+                    down: false
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,474 @@
+//! Which windows Quack may type into.
+//!
+//! Every injection, whether one-shot or streamed, is checked against the
+//! [`InjectionPolicy`] first. Secure desktops and elevated windows are always
+//! refused. Targets on the deny-list, or missing from a non-empty allow-list,
+//! are refused as well. Targets on the confirm-list need the user's approval:
+//! the first attempt fails with [`QuackError::ConfirmationRequired`], and
+//! the frontend retries with the returned token once the user agrees.
+
+use crate::error::QuackError;
+use crate::platform::{Platform, WindowId};
+use crate::window_selector::process_stem;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// How long the user has to approve an injection.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Matches a target window by process, window class or title, case-insensitively.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum TargetRule {
+    /// Executable name, with or without the `.exe` extension.
+    ProcessName(String),
+    /// Window class (`GetClassNameW` / `WM_CLASS`).
+    Class(String),
+    /// Part of the window title, e.g. a bank's name shown in a browser tab.
+    Title(String),
+}
+
+impl TargetRule {
+    pub fn process(name: &str) -> Self {
+        TargetRule::ProcessName(name.to_string())
+    }
+
+    pub fn class(name: &str) -> Self {
+        TargetRule::Class(name.to_string())
+    }
+
+    pub fn matches(&self, target: &Target) -> bool {
+        match self {
+            TargetRule::ProcessName(name) => target
+                .process_name
+                .as_ref()
+                .is_some_and(|p| process_stem(p) == process_stem(name)),
+            TargetRule::Class(class) => target
+                .class
+                .as_ref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(class)),
+            TargetRule::Title(part) => target.title.to_lowercase().contains(&part.to_lowercase()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct InjectionPolicy {
+    /// Never inject into these.
+    pub deny: Vec<TargetRule>,
+    /// When not empty, only inject into these.
+    pub allow: Vec<TargetRule>,
+    /// Ask the user before injecting into these.
+    pub confirm: Vec<TargetRule>,
+    /// Ask the user before every injection.
+    pub confirm_all: bool,
+}
+
+impl Default for InjectionPolicy {
+    /// Password managers are denied; terminals and admin consoles, where a
+    /// stray Enter runs a command, need confirmation.
+    fn default() -> Self {
+        InjectionPolicy {
+            deny: vec![
+                TargetRule::process("KeePass"),
+                TargetRule::process("KeePassXC"),
+                TargetRule::process("1Password"),
+                TargetRule::process("Bitwarden"),
+                TargetRule::process("Dashlane"),
+                TargetRule::class("keepassxc"),
+                TargetRule::class("1password"),
+                TargetRule::class("bitwarden"),
+            ],
+            allow: Vec::new(),
+            confirm: vec![
+                TargetRule::process("cmd"),
+                TargetRule::process("powershell"),
+                TargetRule::process("pwsh"),
+                TargetRule::process("WindowsTerminal"),
+                TargetRule::process("mmc"),
+                TargetRule::process("regedit"),
+                TargetRule::process("putty"),
+                TargetRule::class("ConsoleWindowClass"),
+                TargetRule::class("CASCADIA_HOSTING_WINDOW_CLASS"),
+                TargetRule::class("gnome-terminal-server"),
+                TargetRule::class("konsole"),
+                TargetRule::class("xterm"),
+                TargetRule::class("Alacritty"),
+                TargetRule::class("kitty"),
+            ],
+            confirm_all: false,
+        }
+    }
+}
+
+/// The facts about a window the rules are matched against.
+pub struct Target {
+    title: String,
+    process_name: Option<String>,
+    class: Option<String>,
+}
+
+impl Target {
+    pub fn of(platform: &impl Platform, window: WindowId) -> Self {
+        Target {
+            title: platform.window_title(window),
+            process_name: platform
+                .exe_path(window)
+                .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned())),
+            class: platform.window_class(window),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.title)?;
+        match (&self.process_name, &self.class) {
+            (Some(name), _) => write!(f, " ({})", name),
+            (None, Some(class)) => write!(f, " ({})", class),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// An injection waiting for the user's approval.
+struct PendingConfirmation {
+    token: u64,
+    window: WindowId,
+    requested_at: Instant,
+}
+
+/// The process-wide injection policy.
+pub fn policy() -> &'static Mutex<InjectionPolicy> {
+    static POLICY: OnceLock<Mutex<InjectionPolicy>> = OnceLock::new();
+    POLICY.get_or_init(|| Mutex::new(InjectionPolicy::default()))
+}
+
+/// Only the latest request can be confirmed.
+static PENDING: Mutex<Option<PendingConfirmation>> = Mutex::new(None);
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Takes the pending confirmation if `token` approves injecting into `window`.
+fn take_confirmation(token: u64, window: WindowId) -> bool {
+    let Ok(mut pending) = PENDING.lock() else {
+        return false;
+    };
+    let valid = pending.as_ref().is_some_and(|p| {
+        p.token == token && p.window == window && p.requested_at.elapsed() < CONFIRMATION_TIMEOUT
+    });
+    if valid {
+        *pending = None;
+    }
+    valid
+}
+
+/// Checks whether Quack may inject into `window` under the process-wide
+/// [`policy`].
+///
+/// `confirmation` is the token of an earlier `ConfirmationRequired` error the
+/// user has approved. It only counts for the window it was issued for.
+pub fn authorize(
+    platform: &impl Platform,
+    window: WindowId,
+    confirmation: Option<u64>,
+) -> Result<(), QuackError> {
+    let policy = policy().lock()?.clone();
+    policy.check(platform, window, confirmation)
+}
+
+impl InjectionPolicy {
+    /// [`authorize`] against this policy.
+    fn check(
+        &self,
+        platform: &impl Platform,
+        window: WindowId,
+        confirmation: Option<u64>,
+    ) -> Result<(), QuackError> {
+        if platform.on_secure_desktop() {
+            return Err(QuackError::SecureDesktop);
+        }
+        let target = Target::of(platform, window);
+        if platform.is_elevated(window) {
+            return Err(QuackError::ElevatedTarget {
+                target: target.to_string(),
+            });
+        }
+
+        if self.deny.iter().any(|r| r.matches(&target)) {
+            return Err(QuackError::InjectionDenied {
+                target: target.to_string(),
+                reason: "it is on the deny-list".to_string(),
+            });
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|r| r.matches(&target)) {
+            return Err(QuackError::InjectionDenied {
+                target: target.to_string(),
+                reason: "it is not on the allow-list".to_string(),
+            });
+        }
+
+        let needs_confirmation =
+            self.confirm_all || self.confirm.iter().any(|r| r.matches(&target));
+        if !needs_confirmation || confirmation.is_some_and(|t| take_confirmation(t, window)) {
+            return Ok(());
+        }
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut pending) = PENDING.lock() {
+            *pending = Some(PendingConfirmation {
+                token,
+                window,
+                requested_at: Instant::now(),
+            });
+        }
+        Err(QuackError::ConfirmationRequired {
+            token,
+            target: target.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injection::{plan::InputEvent, PasteContent};
+    use crate::platform::{MonitorInfo, NativeWindow, ScreenCapture, ScreenTarget, WindowBounds};
+    use image::RgbaImage;
+    use std::path::PathBuf;
+
+    /// Every window has the same facts; the policy never needs more than
+    /// these, so the rest is left unimplemented.
+    #[derive(Default)]
+    struct FakePlatform {
+        title: &'static str,
+        exe: Option<&'static str>,
+        class: Option<&'static str>,
+        elevated: bool,
+        secure_desktop: bool,
+    }
+
+    impl Platform for FakePlatform {
+        fn foreground_window(&self) -> Option<WindowId> {
+            unimplemented!()
+        }
+        fn list_windows(&self) -> Vec<NativeWindow> {
+            unimplemented!()
+        }
+        fn window_title(&self, _window: WindowId) -> String {
+            self.title.to_string()
+        }
+        fn window_pid(&self, _window: WindowId) -> Option<u32> {
+            unimplemented!()
+        }
+        fn window_class(&self, _window: WindowId) -> Option<String> {
+            self.class.map(str::to_string)
+        }
+        fn exe_path(&self, _window: WindowId) -> Option<PathBuf> {
+            self.exe.map(PathBuf::from)
+        }
+        fn window_icon_base64(&self, _window: WindowId) -> Option<String> {
+            unimplemented!()
+        }
+        fn watch_foreground(
+            &self,
+            _on_change: &mut dyn FnMut(Option<WindowId>, String),
+        ) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn focus_window(&self, _window: WindowId) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn send_input(&self, _events: &[InputEvent]) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn is_elevated(&self, _window: WindowId) -> bool {
+            self.elevated
+        }
+        fn on_secure_desktop(&self) -> bool {
+            self.secure_desktop
+        }
+        fn inject_text(&self, _window: WindowId, _text: &str) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn paste_text(&self, _window: WindowId, _content: &PasteContent) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError> {
+            unimplemented!()
+        }
+        fn capture_screen(&self, _target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+            unimplemented!()
+        }
+        fn capture_region(&self, _bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+        fn capture_window(&self, _window: WindowId) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+    }
+
+    fn window(raw: isize) -> WindowId {
+        WindowId::from_raw(raw).unwrap()
+    }
+
+    fn target(title: &str, process_name: Option<&str>, class: Option<&str>) -> Target {
+        Target {
+            title: title.to_string(),
+            process_name: process_name.map(str::to_string),
+            class: class.map(str::to_string),
+        }
+    }
+
+    fn empty_policy() -> InjectionPolicy {
+        InjectionPolicy {
+            deny: Vec::new(),
+            allow: Vec::new(),
+            confirm: Vec::new(),
+            confirm_all: false,
+        }
+    }
+
+    fn keepass() -> FakePlatform {
+        FakePlatform {
+            title: "Database - KeePassXC",
+            exe: Some("/usr/bin/keepassxc"),
+            class: Some("KeePassXC"),
+            ..Default::default()
+        }
+    }
+
+    fn editor() -> FakePlatform {
+        FakePlatform {
+            title: "notes.txt - Notepad",
+            exe: Some("C:/Windows/notepad.exe"),
+            class: Some("Notepad"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rules_match_case_insensitively() {
+        let notepad = target("Notes - Notepad", Some("Notepad.exe"), Some("Notepad"));
+        let bank = target("My BANK - Firefox", Some("firefox"), None);
+        let cases = [
+            (TargetRule::process("notepad"), &notepad, true),
+            (TargetRule::process("NOTEPAD.EXE"), &notepad, true),
+            (TargetRule::process("note"), &notepad, false),
+            (TargetRule::process("firefox.exe"), &bank, true),
+            (TargetRule::class("notepad"), &notepad, true),
+            (TargetRule::class("Note"), &notepad, false),
+            (TargetRule::class("firefox"), &bank, false),
+            (TargetRule::Title("bank".to_string()), &bank, true),
+            (TargetRule::Title("NOTES".to_string()), &notepad, true),
+            (TargetRule::Title("chrome".to_string()), &bank, false),
+        ];
+        for (rule, target, expected) in cases {
+            assert_eq!(rule.matches(target), expected, "{:?} on {}", rule, target);
+        }
+    }
+
+    #[test]
+    fn refuses_secure_desktop_and_elevated_targets() {
+        let policy = empty_policy();
+        let secure = FakePlatform {
+            secure_desktop: true,
+            ..editor()
+        };
+        assert!(matches!(
+            policy.check(&secure, window(1), None),
+            Err(QuackError::SecureDesktop)
+        ));
+        let elevated = FakePlatform {
+            elevated: true,
+            ..editor()
+        };
+        assert!(matches!(
+            policy.check(&elevated, window(1), None),
+            Err(QuackError::ElevatedTarget { .. })
+        ));
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        let policy = InjectionPolicy {
+            deny: vec![TargetRule::process("keepassxc")],
+            allow: vec![TargetRule::class("keepassxc")],
+            ..empty_policy()
+        };
+        let Err(QuackError::InjectionDenied { reason, .. }) =
+            policy.check(&keepass(), window(1), None)
+        else {
+            panic!("expected the deny-list to refuse");
+        };
+        assert_eq!(reason, "it is on the deny-list");
+    }
+
+    #[test]
+    fn allow_list_only_restricts_when_not_empty() {
+        let empty = empty_policy();
+        assert!(empty.check(&editor(), window(1), None).is_ok());
+        assert!(empty.check(&keepass(), window(1), None).is_ok());
+
+        let allow_editor = InjectionPolicy {
+            allow: vec![TargetRule::process("notepad")],
+            ..empty_policy()
+        };
+        assert!(allow_editor.check(&editor(), window(1), None).is_ok());
+        let Err(QuackError::InjectionDenied { reason, .. }) =
+            allow_editor.check(&keepass(), window(1), None)
+        else {
+            panic!("expected the allow-list to refuse");
+        };
+        assert_eq!(reason, "it is not on the allow-list");
+    }
+
+    // Confirmations share the process-wide pending slot, so every case that
+    // issues one runs in this single test.
+    #[test]
+    fn confirmation_tokens_are_single_use_per_window_and_expire() {
+        let confirm_all = InjectionPolicy {
+            confirm_all: true,
+            ..empty_policy()
+        };
+        let confirm_notepad = InjectionPolicy {
+            confirm: vec![TargetRule::process("notepad")],
+            ..empty_policy()
+        };
+        let required =
+            |policy: &InjectionPolicy, raw: isize, confirmation: Option<u64>| match policy.check(
+                &editor(),
+                window(raw),
+                confirmation,
+            ) {
+                Err(QuackError::ConfirmationRequired { token, .. }) => token,
+                other => panic!("expected a confirmation request, got {:?}", other),
+            };
+
+        // Not on the confirm-list, so no confirmation unless asked for all.
+        assert!(confirm_notepad.check(&keepass(), window(1), None).is_ok());
+        let token = required(&confirm_all, 1, None);
+        assert!(confirm_all.check(&editor(), window(1), Some(token)).is_ok());
+
+        // Used once already.
+        let token = required(&confirm_notepad, 1, Some(token));
+        assert!(confirm_notepad
+            .check(&editor(), window(1), Some(token))
+            .is_ok());
+
+        // Issued for window 1, so it doesn't approve window 2.
+        let token = required(&confirm_notepad, 1, None);
+        let token = required(&confirm_notepad, 2, Some(token));
+
+        // Left unanswered for too long.
+        if let Some(pending) = PENDING.lock().unwrap().as_mut() {
+            pending.requested_at = Instant::now() - CONFIRMATION_TIMEOUT;
+        }
+        required(&confirm_notepad, 2, Some(token));
+    }
+}
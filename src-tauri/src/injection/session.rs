@@ -0,0 +1,198 @@
+//! Streaming injection: typing an answer into the target while it is still
+//! being generated.
+//!
+//! A session owns a worker thread and a queue of chunks. Pushing a chunk only
+//! appends to the queue, so the token stream is never held up by typing.
+//! Chunks that arrive while the worker is busy are typed together as one
+//! batch. Before each batch the worker checks that the target still has focus
+//! and brings it back if the user switched away.
+
+use super::{history, plan::plan_text, policy, InjectionMode};
+use crate::error::QuackError;
+use crate::platform::{Platform, WindowId};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Time the target gets to process activation before we type into it.
+const FOCUS_DELAY: Duration = Duration::from_millis(50);
+
+/// What a session ended up doing, returned when it ends or is cancelled.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct InjectionSummary {
+    /// The text that was actually typed into the target.
+    pub typed: String,
+    /// Characters that were pushed but never typed, because the session was
+    /// cancelled or stopped on an error.
+    pub dropped_chars: usize,
+    /// How often the target had to be brought back to the foreground.
+    pub refocus_count: u32,
+    pub cancelled: bool,
+    /// The error that stopped the session early, if any.
+    pub error: Option<QuackError>,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<String>,
+    paused: bool,
+    /// No more chunks will be pushed; the worker stops once the queue is empty.
+    ending: bool,
+    cancelled: bool,
+    summary: InjectionSummary,
+}
+
+impl State {
+    fn drop_queue(&mut self) {
+        let dropped: usize = self.queue.drain(..).map(|c| c.chars().count()).sum();
+        self.summary.dropped_chars += dropped;
+    }
+}
+
+type Shared = Arc<(Mutex<State>, Condvar)>;
+
+fn lock(shared: &Shared) -> MutexGuard<'_, State> {
+    shared.0.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A running streaming injection into one window.
+pub struct InjectionSession {
+    target: WindowId,
+    shared: Shared,
+    worker: JoinHandle<()>,
+}
+
+impl InjectionSession {
+    /// Checks `target` against the injection policy and starts the worker that
+    /// focuses it and types pushed chunks into it.
+    pub fn begin(
+        platform: &'static impl Platform,
+        target: WindowId,
+        confirmation: Option<u64>,
+    ) -> Result<Self, QuackError> {
+        policy::authorize(platform, target, confirmation)?;
+        let shared: Shared = Default::default();
+        let worker = {
+            let shared = shared.clone();
+            thread::spawn(move || run(platform, target, &shared))
+        };
+        Ok(InjectionSession {
+            target,
+            shared,
+            worker,
+        })
+    }
+
+    /// Queues `chunk` for typing. Fails once the worker has stopped on an
+    /// error, so the caller can stop streaming.
+    pub fn push(&self, chunk: String) -> Result<(), QuackError> {
+        let mut state = lock(&self.shared);
+        if let Some(error) = &state.summary.error {
+            return Err(error.clone());
+        }
+        if !chunk.is_empty() {
+            state.queue.push_back(chunk);
+            self.shared.1.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Stops typing after the current batch. Pushed chunks keep queueing.
+    pub fn pause(&self) {
+        lock(&self.shared).paused = true;
+    }
+
+    pub fn resume(&self) {
+        lock(&self.shared).paused = false;
+        self.shared.1.notify_one();
+    }
+
+    /// Types whatever is still queued (resuming if paused), then stops.
+    pub fn end(self) -> InjectionSummary {
+        {
+            let mut state = lock(&self.shared);
+            state.ending = true;
+            state.paused = false;
+        }
+        self.finish()
+    }
+
+    /// Drops the queued chunks and stops after the batch being typed, if any.
+    pub fn cancel(self) -> InjectionSummary {
+        {
+            let mut state = lock(&self.shared);
+            state.cancelled = true;
+            state.summary.cancelled = true;
+            state.drop_queue();
+        }
+        self.finish()
+    }
+
+    fn finish(self) -> InjectionSummary {
+        self.shared.1.notify_one();
+        let _ = self.worker.join();
+        let summary = std::mem::take(&mut lock(&self.shared).summary);
+        // Undo removes the whole streamed answer at once.
+        history::record(self.target, summary.typed.clone(), InjectionMode::Type);
+        summary
+    }
+}
+
+/// Brings `target` to the foreground unless it already is. Returns whether
+/// it had to.
+fn ensure_focused(platform: &impl Platform, target: WindowId) -> Result<bool, QuackError> {
+    if platform.foreground_window() == Some(target) {
+        return Ok(false);
+    }
+    platform.focus_window(target)?;
+    thread::sleep(FOCUS_DELAY);
+    if platform.foreground_window() != Some(target) {
+        return Err(QuackError::access_denied(
+            "Could not bring the target window back to the foreground",
+        ));
+    }
+    Ok(true)
+}
+
+fn run(platform: &impl Platform, target: WindowId, shared: &Shared) {
+    // The initial activation (away from Quack's own window) isn't a refocus.
+    let mut first = true;
+    loop {
+        let batch: String = {
+            let mut state = lock(shared);
+            loop {
+                if state.cancelled || (state.ending && state.queue.is_empty()) {
+                    return;
+                }
+                if !state.paused && !state.queue.is_empty() {
+                    break;
+                }
+                state = shared.1.wait(state).unwrap_or_else(PoisonError::into_inner);
+            }
+            state.queue.drain(..).collect()
+        };
+
+        let result = ensure_focused(platform, target)
+            .and_then(|refocused| platform.send_input(&plan_text(&batch)).map(|_| refocused));
+
+        let mut state = lock(shared);
+        match result {
+            Ok(refocused) => {
+                state.summary.typed.push_str(&batch);
+                if refocused && !first {
+                    state.summary.refocus_count += 1;
+                }
+            }
+            Err(e) => {
+                state.summary.dropped_chars += batch.chars().count();
+                state.drop_queue();
+                state.summary.error = Some(e);
+                return;
+            }
+        }
+        first = false;
+    }
+}
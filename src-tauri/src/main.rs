@@ -1,20 +1,31 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 // Declare the modules that make up the application logic.
+mod capture;
+mod clipboard_history;
+mod error;
 mod functions;
+mod icon_cache;
+mod injection;
 mod platform;
+mod sensitive;
 mod utils;
+mod window_selector;
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .register_uri_scheme_protocol(capture::URI_SCHEME, capture::serve)
         // Register all the invokable commands from the `commands` module.
         .invoke_handler(tauri::generate_handler![
             functions::overlay::enable_notch,
             functions::overlay::follow_magic_dot,
             functions::overlay::pin_magic_dot,
             functions::general::start_window_watch,
+            functions::general::get_app_icon,
+            functions::general::set_icon_cache_on_disk_enabled,
+            functions::general::get_icon_cache_on_disk_enabled,
             functions::overlay::start_notch_watcher,
             functions::overlay::close_magic_dot,
             functions::overlay::close_magic_chat,
@@ -27,14 +38,40 @@ fn main() {
             functions::overlay::show_magic_dot,
             functions::chat::set_auto_show_on_copy_enabled,
             functions::chat::get_auto_show_on_copy_enabled,
+            functions::chat::set_clipboard_history_enabled,
+            functions::chat::get_clipboard_history_enabled,
+            functions::chat::set_clipboard_history_on_disk_enabled,
+            functions::chat::get_clipboard_history_on_disk_enabled,
+            functions::chat::list_clipboard_history,
+            functions::chat::search_clipboard_history,
+            functions::chat::pin_clip,
+            functions::chat::restore_clip,
+            functions::chat::set_sensitive_data_policy,
+            functions::chat::get_sensitive_data_policy,
             functions::chat::set_auto_show_on_selection_enabled,
             functions::chat::get_auto_show_on_selection_enabled,
             functions::chat::set_quack_watcher_enabled,
             functions::chat::get_quack_watcher_enabled,
             functions::chat::set_notch_window_display_enabled,
             functions::chat::get_notch_window_display_enabled,
+            functions::general::list_windows,
             functions::general::inject_text_to_window_by_title,
+            functions::general::begin_injection,
+            functions::general::push_chunk,
+            functions::general::pause_injection,
+            functions::general::resume_injection,
+            functions::general::end_injection,
+            functions::general::cancel_injection,
+            functions::general::undo_last_injection,
+            functions::general::set_injection_policy,
+            functions::general::get_injection_policy,
+            functions::general::set_redaction_policy,
+            functions::general::get_redaction_policy,
             functions::general::capture_window_screenshot,
+            functions::general::capture_screen,
+            functions::general::capture_region,
+            functions::general::start_region_selection,
+            functions::general::finish_region_selection,
             functions::general::capture_window_screenshot_by_title,
             functions::general::capture_window_screenshot_by_hwnd,
         ])
@@ -0,0 +1,357 @@
+//! Application icon lookup following the freedesktop.org specifications.
+//!
+//! Given a window's `WM_CLASS`, finds the matching `.desktop` entry (by
+//! `StartupWMClass` or desktop file id), reads its `Icon=` key and resolves
+//! that through the icon theme (the user's GTK theme, its `Inherits=` chain,
+//! then `hicolor` and `/usr/share/pixmaps`), picking the directory that best
+//! fits the requested size and scale. SVG icons are rasterised to PNG.
+
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+type IniSections = HashMap<String, HashMap<String, String>>;
+
+/// Minimal `.desktop` / `index.theme` parser: `[Section]` headers and `Key=Value` pairs.
+fn parse_ini(text: &str) -> IniSections {
+    let mut sections = IniSections::new();
+    let mut current: Option<String> = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.to_string());
+            sections.entry(name.to_string()).or_default();
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            if let Some(keys) = sections.get_mut(section) {
+                keys.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    sections
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the spec defaults.
+fn xdg_data_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|h| h.join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    data_home
+        .into_iter()
+        .chain(
+            data_dirs
+                .split(':')
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from),
+        )
+        .collect()
+}
+
+/// Collects every `.desktop` file below `dir`, including vendor subdirectories.
+fn collect_desktop_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, out);
+        } else if path.extension().is_some_and(|e| e == "desktop") {
+            out.push(path);
+        }
+    }
+}
+
+/// Finds the `Icon=` value of the desktop entry for a window's `WM_CLASS`.
+///
+/// An entry whose `StartupWMClass` matches wins over one that only matches by
+/// desktop file id (`firefox.desktop`, `org.gnome.Nautilus.desktop`, ...).
+pub fn desktop_icon_name(instance: &str, class: &str) -> Option<String> {
+    let mut files = Vec::new();
+    for dir in xdg_data_dirs() {
+        collect_desktop_files(&dir.join("applications"), &mut files);
+    }
+
+    let names = [instance.to_lowercase(), class.to_lowercase()];
+    let matches = |value: &str| {
+        names
+            .iter()
+            .any(|n| !n.is_empty() && *n == value.to_lowercase())
+    };
+    let mut by_id: Option<String> = None;
+    for file in files {
+        let Ok(text) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let sections = parse_ini(&text);
+        let Some(entry) = sections.get("Desktop Entry") else {
+            continue;
+        };
+        let Some(icon) = entry.get("Icon").filter(|i| !i.is_empty()) else {
+            continue;
+        };
+        if entry.get("StartupWMClass").is_some_and(|c| matches(c)) {
+            return Some(icon.clone());
+        }
+        if by_id.is_none() {
+            let id = file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let last_segment = id.rsplit('.').next().unwrap_or(id);
+            if matches(id) || matches(last_segment) {
+                by_id = Some(icon.clone());
+            }
+        }
+    }
+    by_id
+}
+
+/// Base directories that may contain icon themes, in lookup order.
+fn icon_base_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".icons"));
+    }
+    dirs.extend(xdg_data_dirs().into_iter().map(|d| d.join("icons")));
+    dirs
+}
+
+/// The user's icon theme from GTK settings, if configured.
+fn user_icon_theme() -> Option<String> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    ["gtk-4.0/settings.ini", "gtk-3.0/settings.ini"]
+        .iter()
+        .find_map(|file| {
+            let text = std::fs::read_to_string(config.join(file)).ok()?;
+            parse_ini(&text)
+                .get("Settings")?
+                .get("gtk-icon-theme-name")
+                .map(|t| t.trim_matches('"').to_string())
+        })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DirType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// One `Directories=` entry of an `index.theme`.
+struct ThemeDir {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: DirType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl ThemeDir {
+    fn parse(path: &str, keys: &HashMap<String, String>) -> Option<Self> {
+        let num = |key: &str| keys.get(key).and_then(|v| v.parse::<u32>().ok());
+        let size = num("Size")?;
+        Some(ThemeDir {
+            path: path.to_string(),
+            size,
+            scale: num("Scale").unwrap_or(1),
+            kind: match keys.get("Type").map(String::as_str) {
+                Some("Fixed") => DirType::Fixed,
+                Some("Scalable") => DirType::Scalable,
+                _ => DirType::Threshold,
+            },
+            min_size: num("MinSize").unwrap_or(size),
+            max_size: num("MaxSize").unwrap_or(size),
+            threshold: num("Threshold").unwrap_or(2),
+        })
+    }
+
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            DirType::Fixed => self.size == size,
+            DirType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirType::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (low, high) = match self.kind {
+            DirType::Fixed => (self.size, self.size),
+            DirType::Scalable => (self.min_size, self.max_size),
+            DirType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let (low, high) = (low * self.scale, high * self.scale);
+        if wanted < low {
+            low - wanted
+        } else {
+            wanted.saturating_sub(high)
+        }
+    }
+}
+
+struct Theme {
+    /// Every base directory that has a folder for this theme.
+    roots: Vec<PathBuf>,
+    dirs: Vec<ThemeDir>,
+    inherits: Vec<String>,
+}
+
+fn load_theme(name: &str, base_dirs: &[PathBuf]) -> Option<Theme> {
+    let roots: Vec<PathBuf> = base_dirs
+        .iter()
+        .map(|b| b.join(name))
+        .filter(|p| p.is_dir())
+        .collect();
+    let text = roots
+        .iter()
+        .find_map(|r| std::fs::read_to_string(r.join("index.theme")).ok())?;
+    let sections = parse_ini(&text);
+    let header = sections.get("Icon Theme")?;
+    let list = |key: &str| -> Vec<String> {
+        header
+            .get(key)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let dirs = list("Directories")
+        .into_iter()
+        .chain(list("ScaledDirectories"))
+        .filter_map(|d| ThemeDir::parse(&d, sections.get(&d)?))
+        .collect();
+    Some(Theme {
+        roots,
+        dirs,
+        inherits: list("Inherits"),
+    })
+}
+
+const ICON_EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+/// The spec's `LookupIcon`: an exact size match if any, else the closest size.
+fn lookup_in_theme(theme: &Theme, icon: &str, size: u32, scale: u32) -> Option<PathBuf> {
+    let candidates = || {
+        theme.dirs.iter().flat_map(move |dir| {
+            theme.roots.iter().flat_map(move |root| {
+                ICON_EXTENSIONS.iter().filter_map(move |ext| {
+                    let path = root.join(&dir.path).join(format!("{}.{}", icon, ext));
+                    path.is_file().then_some((dir, path))
+                })
+            })
+        })
+    };
+    if let Some((_, path)) = candidates().find(|(dir, _)| dir.matches_size(size, scale)) {
+        return Some(path);
+    }
+    candidates()
+        .min_by_key(|(dir, _)| dir.size_distance(size, scale))
+        .map(|(_, path)| path)
+}
+
+/// Resolves an icon name through the user's theme, its parents, `hicolor`,
+/// and finally the unthemed `pixmaps` directories.
+pub fn find_icon_file(icon: &str, size: u32, scale: u32) -> Option<PathBuf> {
+    let icon_path = Path::new(icon);
+    if icon_path.is_absolute() {
+        return icon_path.is_file().then(|| icon_path.to_path_buf());
+    }
+
+    let base_dirs = icon_base_dirs();
+    let mut queue: Vec<String> = user_icon_theme().into_iter().collect();
+    queue.push("hicolor".to_string());
+    let mut visited: Vec<String> = Vec::new();
+    while !queue.is_empty() {
+        let name = queue.remove(0);
+        if visited.contains(&name) {
+            continue;
+        }
+        visited.push(name.clone());
+        let Some(theme) = load_theme(&name, &base_dirs) else {
+            continue;
+        };
+        if let Some(path) = lookup_in_theme(&theme, icon, size, scale) {
+            return Some(path);
+        }
+        // Parents go before the trailing hicolor fallback.
+        let at = queue.len().saturating_sub(1);
+        for (i, parent) in theme.inherits.into_iter().enumerate() {
+            queue.insert(at + i, parent);
+        }
+    }
+
+    xdg_data_dirs()
+        .into_iter()
+        .map(|d| d.join("pixmaps"))
+        .chain(std::iter::once(PathBuf::from("/usr/share/pixmaps")))
+        .flat_map(|d| ICON_EXTENSIONS.map(|ext| d.join(format!("{}.{}", icon, ext))))
+        .find(|p| p.is_file())
+}
+
+/// Loads an icon file as PNG bytes, rasterising SVGs at `size * scale` pixels.
+pub fn load_icon_png(path: &Path, size: u32, scale: u32) -> Option<Vec<u8>> {
+    let data = std::fs::read(path).ok()?;
+    if path.extension().is_some_and(|e| e == "svg") {
+        return rasterize_svg(&data, size * scale);
+    }
+    // Make sure it is actually a PNG before handing it out as one.
+    image::load_from_memory_with_format(&data, image::ImageFormat::Png).ok()?;
+    Some(data)
+}
+
+fn rasterize_svg(data: &[u8], px: u32) -> Option<Vec<u8>> {
+    use resvg::{tiny_skia, usvg};
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default()).ok()?;
+    let mut pixmap = tiny_skia::Pixmap::new(px, px)?;
+    let svg_size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        px as f32 / svg_size.width(),
+        px as f32 / svg_size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    pixmap.encode_png().ok()
+}
+
+/// Resolves a window's icon from its `WM_CLASS` via desktop entries and the
+/// icon theme. Falls back to using the class itself as an icon name.
+pub fn icon_png_for_class(instance: &str, class: &str, size: u32, scale: u32) -> Option<Vec<u8>> {
+    let names = desktop_icon_name(instance, class)
+        .into_iter()
+        .chain([class.to_lowercase(), instance.to_lowercase()])
+        .filter(|n| !n.is_empty());
+    for name in names {
+        if let Some(png) =
+            find_icon_file(&name, size, scale).and_then(|path| load_icon_png(&path, size, scale))
+        {
+            return Some(png);
+        }
+    }
+    None
+}
@@ -0,0 +1,235 @@
+//! Cross-platform access to native top-level windows.
+//!
+//! Everything the commands need from the OS (the foreground window, its title,
+//! owning executable and icon, screenshots and synthetic keyboard input) goes
+//! through the [`Platform`] trait. Exactly one backend is compiled in: Win32 on
+//! Windows and X11 (EWMH) on Linux. Use [`native()`] to get at it.
+
+use crate::error::QuackError;
+use crate::injection::{plan::InputEvent, PasteContent};
+use image::RgbaImage;
+use std::{path::PathBuf, sync::OnceLock};
+
+#[cfg(target_os = "linux")]
+mod freedesktop;
+#[cfg(target_os = "windows")]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+/// The backend selected for the current target.
+#[cfg(target_os = "windows")]
+pub type NativePlatform = win32::Win32Platform;
+#[cfg(target_os = "linux")]
+pub type NativePlatform = x11::X11Platform;
+
+/// Opaque handle to a top-level window.
+///
+/// Wraps an `HWND` on Windows and an X11 window id on Linux. The raw value is
+/// what the frontend sees (e.g. the `hwnd` field of `active_window_changed`)
+/// and hands back to commands such as `capture_window_screenshot_by_hwnd`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct WindowId(isize);
+
+impl WindowId {
+    /// Wraps a raw handle received from the frontend. Returns `None` for the null handle.
+    pub fn from_raw(raw: isize) -> Option<Self> {
+        (raw != 0).then_some(Self(raw))
+    }
+
+    /// The raw handle value, suitable for sending to the frontend.
+    pub fn as_raw(self) -> isize {
+        self.0
+    }
+}
+
+/// A rectangle in physical screen pixels, e.g. the outer bounds of a window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WindowBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A top-level window as reported by [`Platform::list_windows`].
+pub struct NativeWindow {
+    pub id: WindowId,
+    pub bounds: WindowBounds,
+    /// Hidden by the compositor, e.g. on another virtual desktop.
+    pub cloaked: bool,
+    /// Tool palette, dock or similar window that wouldn't show up in Alt+Tab.
+    pub tool_window: bool,
+}
+
+/// A display as reported by [`Platform::monitors`].
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MonitorInfo {
+    /// Position in [`Platform::monitors`]; the primary monitor comes first.
+    pub index: usize,
+    /// Device name, e.g. `\\.\DISPLAY1` on Windows.
+    pub name: String,
+    /// Bounds in physical pixels, in virtual-desktop coordinates.
+    pub bounds: WindowBounds,
+    /// Physical pixels per logical pixel, e.g. 1.5 at 150% display scaling.
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+/// What [`Platform::capture_screen`] captures.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ScreenTarget {
+    /// The monitor at this index in [`Platform::monitors`].
+    Monitor(usize),
+    /// The primary monitor.
+    #[default]
+    Primary,
+    /// The monitor under the mouse cursor.
+    Cursor,
+    /// The monitor showing the largest part of the window.
+    Window(WindowId),
+    /// The bounding box of all monitors.
+    VirtualDesktop,
+}
+
+/// A screenshot from [`Platform::capture_screen`].
+pub struct ScreenCapture {
+    pub image: RgbaImage,
+    /// The captured area in physical pixels, in virtual-desktop coordinates.
+    pub bounds: WindowBounds,
+    /// The captured monitor, or `None` for the whole virtual desktop.
+    pub monitor: Option<MonitorInfo>,
+}
+
+/// Clipboard contents in the richest form we understand.
+pub enum ClipContent {
+    Text(String),
+    /// An HTML fragment, e.g. a selection copied in a browser.
+    Html {
+        fragment: String,
+        /// The page it was copied from, when the browser says so.
+        source_url: Option<String>,
+        /// The plain-text version offered alongside.
+        text: Option<String>,
+    },
+    /// Rich text, e.g. copied in a word processor.
+    Rtf {
+        rtf: String,
+        text: Option<String>,
+    },
+    /// A bitmap, e.g. a screenshot or an image copied in a browser.
+    Image(RgbaImage),
+    /// Files and folders copied in a file manager.
+    Files(Vec<PathBuf>),
+}
+
+impl ClipContent {
+    /// The plain-text version, if the clipboard owner offered one.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            ClipContent::Text(text) => Some(text),
+            ClipContent::Html { text, .. } | ClipContent::Rtf { text, .. } => text.as_deref(),
+            ClipContent::Image(_) | ClipContent::Files(_) => None,
+        }
+    }
+}
+
+/// The clipboard contents after a change, as seen by a [`ClipboardWatcher`].
+pub struct ClipboardChange {
+    /// What was copied, or `None` for formats we don't understand.
+    pub content: Option<ClipContent>,
+    /// The owner asked for the content to be kept out of clipboard history,
+    /// as password managers do.
+    pub exclude_from_history: bool,
+}
+
+/// Clipboard change notifications, implemented once per windowing system.
+pub trait ClipboardWatcher {
+    /// Blocks the calling thread and reports every clipboard change through
+    /// `on_change` until it returns `false`. Sleeps in between rather than
+    /// polling. Content whose owner asked clipboard monitors to skip it, such
+    /// as the temporary clipboard of our own paste injection, isn't reported.
+    fn watch_clipboard(
+        &self,
+        on_change: &mut dyn FnMut(ClipboardChange) -> bool,
+    ) -> Result<(), QuackError>;
+}
+
+/// Window, capture and input primitives implemented once per windowing system.
+pub trait Platform: Send + Sync {
+    /// The window that currently has keyboard focus, if any.
+    fn foreground_window(&self) -> Option<WindowId>;
+
+    /// Visible top-level windows in z-order, topmost first.
+    fn list_windows(&self) -> Vec<NativeWindow>;
+
+    /// The window title, or an empty string when it has none.
+    fn window_title(&self, window: WindowId) -> String;
+
+    /// Id of the process that owns the window.
+    fn window_pid(&self, window: WindowId) -> Option<u32>;
+
+    /// Window class name (`GetClassNameW` on Windows, the `WM_CLASS` class on X11).
+    fn window_class(&self, window: WindowId) -> Option<String>;
+
+    /// Full path of the executable that owns the window.
+    fn exe_path(&self, window: WindowId) -> Option<PathBuf>;
+
+    /// Best available icon for the window as a Base64 encoded PNG.
+    fn window_icon_base64(&self, window: WindowId) -> Option<String>;
+
+    /// Stable key identifying the application that owns the window, used to
+    /// cache its icon. Defaults to the executable path.
+    fn icon_key(&self, window: WindowId) -> Option<String> {
+        self.exe_path(window)
+            .map(|p| p.to_string_lossy().into_owned())
+    }
+
+    /// Blocks the calling thread and reports the focused window and its title
+    /// through `on_change`. Only returns if the backend can no longer watch.
+    fn watch_foreground(
+        &self,
+        on_change: &mut dyn FnMut(Option<WindowId>, String),
+    ) -> Result<(), QuackError>;
+
+    /// Brings the window to the foreground and gives it keyboard focus.
+    fn focus_window(&self, window: WindowId) -> Result<(), QuackError>;
+
+    /// Sends key events to whichever window has keyboard focus.
+    fn send_input(&self, events: &[InputEvent]) -> Result<(), QuackError>;
+
+    /// Whether the window belongs to a process with more privileges than a
+    /// regular user's (an elevated process on Windows, root on Linux).
+    fn is_elevated(&self, window: WindowId) -> bool;
+
+    /// Whether a secure desktop (UAC prompt, lock screen) currently has input.
+    fn on_secure_desktop(&self) -> bool;
+
+    /// Types `text` into the window as synthetic keyboard input.
+    fn inject_text(&self, window: WindowId, text: &str) -> Result<(), QuackError>;
+
+    /// Pastes `content` into the window via the clipboard, restoring the
+    /// previous clipboard contents afterwards. `Unsupported` and
+    /// `ClipboardFailed` mean nothing was pasted; other errors may come after
+    /// the paste shortcut was sent.
+    fn paste_text(&self, window: WindowId, content: &PasteContent) -> Result<(), QuackError>;
+
+    /// Connected monitors, the primary one first.
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError>;
+
+    /// Captures a monitor or the whole virtual desktop in physical pixels.
+    fn capture_screen(&self, target: ScreenTarget) -> Result<ScreenCapture, QuackError>;
+
+    /// Captures a rectangle of the virtual desktop, given in physical pixels.
+    fn capture_region(&self, bounds: WindowBounds) -> Result<RgbaImage, QuackError>;
+
+    /// Captures the contents of a single window.
+    fn capture_window(&self, window: WindowId) -> Result<RgbaImage, QuackError>;
+}
+
+/// Returns the process-wide platform backend, initializing it on first use.
+pub fn native() -> &'static NativePlatform {
+    static NATIVE: OnceLock<NativePlatform> = OnceLock::new();
+    NATIVE.get_or_init(NativePlatform::new)
+}
@@ -0,0 +1,684 @@
+//! This module contains Windows-specific functionality using the winapi crate.
+//! It handles getting information about the active window, its process, and its icon,
+//! capturing the screen or a window, and injecting keyboard input.
+
+use super::{
+    ClipboardChange, ClipboardWatcher, MonitorInfo, NativeWindow, Platform, ScreenCapture,
+    ScreenTarget, WindowBounds, WindowId,
+};
+use crate::error::QuackError;
+use crate::injection::{
+    plan::{plan_text, InputEvent, KeyStroke, NamedKey},
+    PasteContent,
+};
+use base64::{engine::general_purpose, Engine as _};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder, RgbaImage};
+use std::{
+    ffi::OsString,
+    mem,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
+    ptr,
+};
+use winapi::{
+    ctypes::c_void,
+    shared::{
+        minwindef::{BOOL, DWORD, LPARAM, TRUE},
+        windef::{HBITMAP, HICON, HWND, RECT},
+        winerror::S_OK,
+    },
+    um::{
+        dwmapi::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+        handleapi::CloseHandle,
+        processthreadsapi::{OpenProcess, OpenProcessToken},
+        psapi::GetModuleFileNameExW,
+        securitybaseapi::GetTokenInformation,
+        shellapi::{SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON},
+        wingdi::{BitBlt, SRCCOPY},
+        winnt::{
+            TokenElevation, HANDLE, PROCESS_QUERY_INFORMATION, PROCESS_QUERY_LIMITED_INFORMATION,
+            PROCESS_VM_READ, TOKEN_ELEVATION, TOKEN_QUERY,
+        },
+        winuser::{
+            CloseDesktop, DestroyIcon, EnumWindows, GetClassNameW, GetForegroundWindow,
+            GetIconInfo, GetUserObjectInformationW, GetWindowLongW, GetWindowRect,
+            GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
+            OpenInputDesktop, PrintWindow, SendInput, SendMessageW, SetForegroundWindow,
+            DESKTOP_READOBJECTS, GWL_EXSTYLE, ICONINFO, ICON_BIG, ICON_SMALL, ICON_SMALL2, INPUT,
+            INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, UOI_NAME, VK_BACK,
+            VK_CONTROL, VK_RETURN, VK_TAB, WM_GETICON, WS_EX_TOOLWINDOW,
+        },
+    },
+};
+
+pub mod clipboard;
+mod gdi;
+mod monitor;
+
+use gdi::{Bitmap, WindowDc};
+use monitor::PhysicalDpiScope;
+
+// Windows crate (WinRT/COM) for packaged app icons
+#[cfg(target_os = "windows")]
+mod packaged_icon {
+    use super::gdi::Bitmap;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::{ComInterface, PCWSTR, PWSTR};
+    use windows::Win32::Foundation::{HWND as WHWND, SIZE};
+    use windows::Win32::Graphics::Gdi::HBITMAP as WHBITMAP;
+    use windows::Win32::UI::Shell::PropertiesSystem::{IPropertyStore, SHGetPropertyStoreForWindow, PROPERTYKEY};
+    use windows::Win32::UI::Shell::{IShellItem, IShellItemImageFactory, SHCreateItemFromIDList, SHParseDisplayName, SIIGBF_ICONONLY};
+    use windows::Win32::UI::Shell::Common::ITEMIDLIST;
+
+    /// Reads the AppUserModel ID of a packaged (UWP/MSIX) app's window.
+    pub fn try_get_aumid(hwnd: super::HWND) -> Option<String> {
+        unsafe {
+            let hwnd_w = WHWND(hwnd as isize);
+            let store: IPropertyStore = SHGetPropertyStoreForWindow(hwnd_w).ok()?;
+            // PKEY_AppUserModel_ID {9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}, pid 5
+            let pkey = PROPERTYKEY{ fmtid: windows::core::GUID::from_values(0x9f4c2855,0x9f79,0x4b39,[0xa8,0xd0,0xe1,0xd4,0x2d,0xe1,0xd5,0xf3]), pid: 5};
+            let var = store.GetValue(&pkey).ok()?;
+            let pw = PWSTR(var.Anonymous.Anonymous.Anonymous.pwszVal.0);
+            if pw.is_null() { return None; }
+            pw.to_string().ok()
+        }
+    }
+
+    pub fn try_get_packaged_icon(hwnd: super::HWND) -> Option<String> {
+        unsafe {
+            let aumid = try_get_aumid(hwnd)?;
+            let target = format!("shell:AppsFolder\\{}", aumid);
+            let target_w: Vec<u16> = OsString::from(target).encode_wide().chain(Some(0)).collect();
+
+            let mut pidl: *mut ITEMIDLIST = std::ptr::null_mut();
+            if SHParseDisplayName(PCWSTR(target_w.as_ptr()), None, &mut pidl, 0, None).is_err() || pidl.is_null() {
+                return None;
+            }
+            let item: IShellItem = SHCreateItemFromIDList(pidl).ok()?;
+            let imgf: IShellItemImageFactory = item.cast().ok()?;
+            let hbmp: WHBITMAP = imgf.GetImage(SIZE{cx:32, cy:32}, SIIGBF_ICONONLY).ok()?;
+            let bitmap = Bitmap::from_raw(hbmp.0 as super::HBITMAP)?;
+            super::png_base64(&bitmap.to_rgba().ok()?)
+        }
+    }
+}
+
+/// Gets the full executable path from a window handle (HWND).
+pub fn exe_path_from_hwnd(hwnd: HWND) -> Option<PathBuf> {
+    unsafe {
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 {
+            return None;
+        }
+
+        let process_handle = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, 0, pid);
+        if process_handle.is_null() {
+            return None;
+        }
+
+        let mut buf = vec![0u16; 260]; // MAX_PATH
+        let len = GetModuleFileNameExW(
+            process_handle,
+            ptr::null_mut(),
+            buf.as_mut_ptr(),
+            buf.len() as u32,
+        );
+        CloseHandle(process_handle);
+
+        if len == 0 {
+            return None;
+        }
+
+        buf.truncate(len as usize);
+        Some(PathBuf::from(OsString::from_wide(&buf)))
+    }
+}
+
+/// Extracts the icon from an executable file and returns it as a Base64 encoded PNG.
+pub fn get_icon_base64_from_exe(exe_path: &PathBuf) -> Option<String> {
+    unsafe {
+        let mut shinfo: SHFILEINFOW = std::mem::zeroed();
+        let exe_wide: Vec<u16> = exe_path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+        if SHGetFileInfoW(
+            exe_wide.as_ptr(),
+            0,
+            &mut shinfo,
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_LARGEICON,
+        ) == 0
+        {
+            return None;
+        }
+
+        if shinfo.hIcon.is_null() {
+            return None;
+        }
+
+        // Unlike window icons, this one is ours to destroy.
+        let base64 = hicon_to_base64_png(shinfo.hIcon);
+        DestroyIcon(shinfo.hIcon);
+        base64
+    }
+}
+
+/// Encodes an image as a Base64 PNG string.
+fn png_base64(image: &RgbaImage) -> Option<String> {
+    let mut png_bytes = Vec::new();
+    PngEncoder::new(&mut png_bytes)
+        .write_image(image, image.width(), image.height(), ColorType::Rgba8)
+        .ok()?;
+    Some(general_purpose::STANDARD.encode(&png_bytes))
+}
+
+/// Converts a Windows icon handle (HICON) to a Base64 encoded PNG string.
+/// The icon itself is left alone; only the caller knows whether it owns it.
+fn hicon_to_base64_png(hicon: HICON) -> Option<String> {
+    let mut icon_info: ICONINFO = unsafe { mem::zeroed() };
+    if unsafe { GetIconInfo(hicon, &mut icon_info) } == 0 {
+        return None;
+    }
+    // GetIconInfo creates both bitmaps for us; the guards delete them.
+    let _mask = Bitmap::from_raw(icon_info.hbmMask);
+    let color = Bitmap::from_raw(icon_info.hbmColor)?;
+    png_base64(&color.to_rgba().ok()?)
+}
+
+/// Tries to fetch the actual window icon via WM_GETICON, then falls back to the
+/// class icon. Returns Base64-encoded PNG when successful.
+pub fn get_window_icon_base64_from_hwnd(hwnd: HWND) -> Option<String> {
+    unsafe {
+        // 1) Ask the window for its icon
+        let mut hicon = SendMessageW(hwnd, WM_GETICON, ICON_BIG as usize, 0) as HICON;
+        if hicon.is_null() {
+            hicon = SendMessageW(hwnd, WM_GETICON, ICON_SMALL as usize, 0) as HICON;
+        }
+        if hicon.is_null() {
+            hicon = SendMessageW(hwnd, WM_GETICON, ICON_SMALL2 as usize, 0) as HICON;
+        }
+
+        // 2) If still null, try the class icon
+        if hicon.is_null() {
+            #[cfg(target_pointer_width = "64")]
+            {
+                use winapi::um::winuser::{GetClassLongPtrW, GCLP_HICON, GCLP_HICONSM};
+                hicon = GetClassLongPtrW(hwnd, GCLP_HICON) as HICON;
+                if hicon.is_null() {
+                    hicon = GetClassLongPtrW(hwnd, GCLP_HICONSM) as HICON;
+                }
+            }
+
+            #[cfg(target_pointer_width = "32")]
+            {
+                use winapi::um::winuser::{GetClassLongW, GCL_HICON, GCL_HICONSM};
+                hicon = GetClassLongW(hwnd, GCL_HICON) as HICON;
+                if hicon.is_null() {
+                    hicon = GetClassLongW(hwnd, GCL_HICONSM) as HICON;
+                }
+            }
+        }
+
+        if hicon.is_null() {
+            return None;
+        }
+
+        hicon_to_base64_png(hicon)
+    }
+}
+
+/// Gets the window title text for a given HWND.
+pub fn get_window_title(hwnd: HWND) -> String {
+    unsafe {
+        let len = GetWindowTextLengthW(hwnd);
+        if len == 0 {
+            return String::new();
+        }
+        let mut buf: Vec<u16> = vec![0; (len + 1) as usize];
+        let copied = GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32);
+        if copied <= 0 {
+            return String::new();
+        }
+        OsString::from_wide(&buf[..copied as usize])
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Gets the window class name for a given HWND.
+pub fn get_window_class(hwnd: HWND) -> Option<String> {
+    unsafe {
+        // Class names are limited to 256 characters.
+        let mut buf: Vec<u16> = vec![0; 257];
+        let copied = GetClassNameW(hwnd, buf.as_mut_ptr(), buf.len() as i32);
+        if copied <= 0 {
+            return None;
+        }
+        Some(
+            OsString::from_wide(&buf[..copied as usize])
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+/// Gets the AppUserModel ID of a packaged app's window, if it has one.
+pub fn get_packaged_app_id_from_hwnd(hwnd: HWND) -> Option<String> {
+    packaged_icon::try_get_aumid(hwnd)
+}
+
+/// Icon of a packaged (UWP/MSIX) app's window as a Base64 encoded PNG, looked
+/// up in the shell's AppsFolder by its AppUserModel ID.
+pub fn get_packaged_app_icon_from_hwnd(hwnd: HWND) -> Option<String> {
+    packaged_icon::try_get_packaged_icon(hwnd)
+}
+
+unsafe extern "system" fn collect_hwnd(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let hwnds = &mut *(lparam as *mut Vec<HWND>);
+    hwnds.push(hwnd);
+    TRUE
+}
+
+/// Enumerates visible top-level windows with `EnumWindows`, which already
+/// reports them in z-order (topmost first).
+pub fn enumerate_windows() -> Vec<NativeWindow> {
+    // Physical bounds, comparable with the monitors and captures.
+    let _dpi = PhysicalDpiScope::enter();
+    let mut hwnds: Vec<HWND> = Vec::new();
+    unsafe {
+        EnumWindows(Some(collect_hwnd), &mut hwnds as *mut Vec<HWND> as LPARAM);
+    }
+    hwnds
+        .into_iter()
+        .filter_map(|hwnd| unsafe {
+            if IsWindowVisible(hwnd) == 0 {
+                return None;
+            }
+            let mut rect: RECT = std::mem::zeroed();
+            if GetWindowRect(hwnd, &mut rect) == 0 {
+                return None;
+            }
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD;
+            let mut cloaked: DWORD = 0;
+            let hr = DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_CLOAKED,
+                &mut cloaked as *mut DWORD as *mut c_void,
+                std::mem::size_of::<DWORD>() as DWORD,
+            );
+            Some(NativeWindow {
+                id: WindowId::from_raw(hwnd as isize)?,
+                bounds: WindowBounds {
+                    x: rect.left,
+                    y: rect.top,
+                    width: (rect.right - rect.left).max(0) as u32,
+                    height: (rect.bottom - rect.top).max(0) as u32,
+                },
+                cloaked: hr == S_OK && cloaked != 0,
+                tool_window: ex_style & WS_EX_TOOLWINDOW != 0,
+            })
+        })
+        .collect()
+}
+
+/// Converts planned key events into `INPUT` records for `SendInput`.
+fn to_send_inputs(events: &[InputEvent]) -> Vec<INPUT> {
+    events
+        .iter()
+        .map(|event| unsafe {
+            let (vk, scan, mut flags) = match event.stroke {
+                KeyStroke::Unicode(unit) => (0, unit, KEYEVENTF_UNICODE),
+                KeyStroke::Named(NamedKey::Enter) => (VK_RETURN as u16, 0, 0),
+                KeyStroke::Named(NamedKey::Tab) => (VK_TAB as u16, 0, 0),
+                KeyStroke::Named(NamedKey::Backspace) => (VK_BACK as u16, 0, 0),
+                KeyStroke::Named(NamedKey::Control) => (VK_CONTROL as u16, 0, 0),
+                // Virtual-key codes for letters are their uppercase ASCII values.
+                KeyStroke::Named(NamedKey::Letter(c)) => (c.to_ascii_uppercase() as u16, 0, 0),
+            };
+            if !event.down {
+                flags |= KEYEVENTF_KEYUP;
+            }
+            let mut input = INPUT {
+                type_: INPUT_KEYBOARD,
+                u: std::mem::zeroed(),
+            };
+            *input.u.ki_mut() = KEYBDINPUT {
+                wVk: vk,
+                wScan: scan,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            };
+            input
+        })
+        .collect()
+}
+
+/// Sends planned key events in a single `SendInput` call, so other input
+/// can't interleave with ours.
+pub fn send_input_events(events: &[InputEvent]) -> Result<(), QuackError> {
+    let mut inputs = to_send_inputs(events);
+    if inputs.is_empty() {
+        return Ok(());
+    }
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_mut_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize != inputs.len() {
+        // Typically UIPI: the target runs elevated and we don't.
+        return Err(QuackError::AccessDenied {
+            message: format!(
+                "SendInput was blocked after {} of {} events",
+                sent,
+                inputs.len()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Whether the process owning `hwnd` runs elevated. Processes we aren't
+/// allowed to inspect (protected or higher-integrity ones) count as elevated.
+pub fn is_window_elevated(hwnd: HWND) -> bool {
+    unsafe {
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 {
+            return false;
+        }
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return true;
+        }
+        let mut token: HANDLE = ptr::null_mut();
+        let opened = OpenProcessToken(process, TOKEN_QUERY, &mut token);
+        CloseHandle(process);
+        if opened == 0 {
+            return true;
+        }
+        let mut elevation: TOKEN_ELEVATION = mem::zeroed();
+        let mut len = 0;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut _ as *mut c_void,
+            mem::size_of::<TOKEN_ELEVATION>() as DWORD,
+            &mut len,
+        );
+        CloseHandle(token);
+        ok != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Whether input currently goes to a desktop other than the user's default
+/// one, i.e. the secure desktop of a UAC prompt or the lock screen.
+pub fn is_secure_desktop_active() -> bool {
+    unsafe {
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_READOBJECTS);
+        // Only Winlogon may open the secure desktop.
+        if desktop.is_null() {
+            return true;
+        }
+        let mut name = [0u16; 64];
+        let mut needed = 0;
+        let ok = GetUserObjectInformationW(
+            desktop as HANDLE,
+            UOI_NAME as i32,
+            name.as_mut_ptr() as *mut c_void,
+            mem::size_of_val(&name) as DWORD,
+            &mut needed,
+        );
+        CloseDesktop(desktop);
+        if ok == 0 {
+            return false;
+        }
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        !String::from_utf16_lossy(&name[..len]).eq_ignore_ascii_case("Default")
+    }
+}
+
+/// Brings the window to the foreground and types `text` into it with `SendInput`.
+pub fn inject_text_to_window(text: &str, hwnd: HWND) -> Result<(), QuackError> {
+    unsafe {
+        if SetForegroundWindow(hwnd) == 0 {
+            return Err(QuackError::access_denied(
+                "Failed to bring target window to foreground",
+            ));
+        }
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    send_input_events(&plan_text(text))
+}
+
+/// Captures a monitor or the whole virtual desktop with `BitBlt`.
+pub fn capture_screen(target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+    let _dpi = PhysicalDpiScope::enter();
+    let monitor = match target {
+        ScreenTarget::Primary => Some(monitor::at_index(0)?),
+        ScreenTarget::Monitor(index) => Some(monitor::at_index(index)?),
+        ScreenTarget::Cursor => Some(monitor::under_cursor()?),
+        ScreenTarget::Window(window) => Some(monitor::of_window(hwnd(window))?),
+        ScreenTarget::VirtualDesktop => None,
+    };
+    let bounds = match &monitor {
+        Some(monitor) => monitor.bounds,
+        None => monitor::virtual_desktop()?,
+    };
+    let image = capture_screen_region(
+        bounds.x,
+        bounds.y,
+        bounds.width as i32,
+        bounds.height as i32,
+    )?;
+    Ok(ScreenCapture {
+        image,
+        bounds,
+        monitor,
+    })
+}
+
+/// Captures a rectangle of the screen, in virtual-screen coordinates, with
+/// `BitBlt`.
+pub fn capture_screen_region(
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+) -> Result<RgbaImage, QuackError> {
+    let _dpi = PhysicalDpiScope::enter();
+    let screen_dc = WindowDc::screen()?;
+    gdi::capture_with(&screen_dc, width, height, |memory_dc| {
+        let copied = unsafe {
+            BitBlt(
+                memory_dc,
+                0,
+                0,
+                width,
+                height,
+                screen_dc.hdc(),
+                x,
+                y,
+                SRCCOPY,
+            )
+        };
+        if copied == 0 {
+            return Err(QuackError::capture("BitBlt"));
+        }
+        Ok(())
+    })
+}
+
+/// Captures a single window, preferring `PrintWindow` so occluded and UWP
+/// windows render correctly, and falling back to `BitBlt` from the window DC.
+pub fn capture_window(hwnd: HWND) -> Result<RgbaImage, QuackError> {
+    let _dpi = PhysicalDpiScope::enter();
+    let mut window_rect: RECT = unsafe { mem::zeroed() };
+    if unsafe { GetWindowRect(hwnd, &mut window_rect) } == 0 {
+        return Err(QuackError::capture("GetWindowRect"));
+    }
+
+    let width = (window_rect.right - window_rect.left).max(0);
+    let height = (window_rect.bottom - window_rect.top).max(0);
+    if width == 0 || height == 0 {
+        return Err(QuackError::capture("window size"));
+    }
+
+    let window_dc = WindowDc::get(hwnd)?;
+    gdi::capture_with(&window_dc, width, height, |memory_dc| unsafe {
+        // Try PrintWindow with full-content first (more robust for occluded/UWP windows)
+        if PrintWindow(hwnd, memory_dc, 2) != 0 || PrintWindow(hwnd, memory_dc, 0) != 0 {
+            return Ok(());
+        }
+        // Fallback to BitBlt from window DC
+        if BitBlt(
+            memory_dc,
+            0,
+            0,
+            width,
+            height,
+            window_dc.hdc(),
+            0,
+            0,
+            SRCCOPY,
+        ) == 0
+        {
+            return Err(QuackError::capture("PrintWindow"));
+        }
+        Ok(())
+    })
+}
+
+/// [`Platform`] backend built on the Win32 API.
+pub struct Win32Platform;
+
+impl Win32Platform {
+    pub fn new() -> Self {
+        Win32Platform
+    }
+}
+
+fn hwnd(window: WindowId) -> HWND {
+    window.as_raw() as HWND
+}
+
+impl Platform for Win32Platform {
+    fn foreground_window(&self) -> Option<WindowId> {
+        let hwnd = unsafe { GetForegroundWindow() };
+        WindowId::from_raw(hwnd as isize)
+    }
+
+    fn list_windows(&self) -> Vec<NativeWindow> {
+        enumerate_windows()
+    }
+
+    fn window_title(&self, window: WindowId) -> String {
+        get_window_title(hwnd(window))
+    }
+
+    fn window_pid(&self, window: WindowId) -> Option<u32> {
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(hwnd(window), &mut pid) };
+        (pid != 0).then_some(pid)
+    }
+
+    fn window_class(&self, window: WindowId) -> Option<String> {
+        get_window_class(hwnd(window))
+    }
+
+    fn exe_path(&self, window: WindowId) -> Option<PathBuf> {
+        exe_path_from_hwnd(hwnd(window))
+    }
+
+    fn window_icon_base64(&self, window: WindowId) -> Option<String> {
+        let hwnd = hwnd(window);
+        get_window_icon_base64_from_hwnd(hwnd)
+            .or_else(|| get_packaged_app_icon_from_hwnd(hwnd))
+            .or_else(|| exe_path_from_hwnd(hwnd).and_then(|p| get_icon_base64_from_exe(&p)))
+    }
+
+    fn icon_key(&self, window: WindowId) -> Option<String> {
+        // Packaged apps all run under hosts like ApplicationFrameHost.exe, so key them by AUMID.
+        let hwnd = hwnd(window);
+        get_packaged_app_id_from_hwnd(hwnd).or_else(|| {
+            exe_path_from_hwnd(hwnd).map(|p| p.to_string_lossy().into_owned())
+        })
+    }
+
+    fn watch_foreground(
+        &self,
+        on_change: &mut dyn FnMut(Option<WindowId>, String),
+    ) -> Result<(), QuackError> {
+        // Win32 has no cheap focus notification without a message loop, so poll.
+        loop {
+            let window = self.foreground_window();
+            let title = window.map(|w| self.window_title(w)).unwrap_or_default();
+            on_change(window, title);
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+
+    fn focus_window(&self, window: WindowId) -> Result<(), QuackError> {
+        if unsafe { SetForegroundWindow(hwnd(window)) } == 0 {
+            return Err(QuackError::access_denied(
+                "Failed to bring target window to foreground",
+            ));
+        }
+        Ok(())
+    }
+
+    fn send_input(&self, events: &[InputEvent]) -> Result<(), QuackError> {
+        send_input_events(events)
+    }
+
+    fn is_elevated(&self, window: WindowId) -> bool {
+        is_window_elevated(hwnd(window))
+    }
+
+    fn on_secure_desktop(&self) -> bool {
+        is_secure_desktop_active()
+    }
+
+    fn inject_text(&self, window: WindowId, text: &str) -> Result<(), QuackError> {
+        inject_text_to_window(text, hwnd(window))
+    }
+
+    fn paste_text(&self, window: WindowId, content: &PasteContent) -> Result<(), QuackError> {
+        clipboard::paste_to_window(hwnd(window), content)
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError> {
+        let _dpi = PhysicalDpiScope::enter();
+        monitor::monitors()
+    }
+
+    fn capture_screen(&self, target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+        capture_screen(target)
+    }
+
+    fn capture_region(&self, bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+        capture_screen_region(
+            bounds.x,
+            bounds.y,
+            bounds.width as i32,
+            bounds.height as i32,
+        )
+    }
+
+    fn capture_window(&self, window: WindowId) -> Result<RgbaImage, QuackError> {
+        capture_window(hwnd(window))
+    }
+}
+
+impl ClipboardWatcher for Win32Platform {
+    fn watch_clipboard(
+        &self,
+        on_change: &mut dyn FnMut(ClipboardChange) -> bool,
+    ) -> Result<(), QuackError> {
+        clipboard::watch(on_change)
+    }
+}
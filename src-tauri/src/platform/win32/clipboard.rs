@@ -0,0 +1,555 @@
+//! Clipboard snapshot/restore, paste-mode injection, change notifications and
+//! reading of rich formats for Windows.
+
+use super::{send_input_events, HWND};
+use crate::error::QuackError;
+use crate::injection::{
+    plan::{plan_chord, NamedKey},
+    PasteContent,
+};
+use crate::platform::{ClipContent, ClipboardChange};
+use image::{codecs::bmp::BmpDecoder, DynamicImage, RgbaImage};
+use std::{
+    ffi::{OsStr, OsString},
+    io, mem,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+use winapi::{
+    shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM},
+    um::{
+        libloaderapi::GetModuleHandleW,
+        winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+        wingdi::{CopyMetaFileW, DeleteMetaFile, METAFILEPICT},
+        winnt::HANDLE,
+        winuser::{
+            AddClipboardFormatListener, CloseClipboard, CreateWindowExW, DefWindowProcW,
+            DestroyWindow, DispatchMessageW, EmptyClipboard, EnumClipboardFormats,
+            GetClipboardData, GetClipboardSequenceNumber, GetMessageW, GetOpenClipboardWindow,
+            IsClipboardFormatAvailable, OpenClipboard, PostMessageW, RegisterClassW,
+            RegisterClipboardFormatW, RemoveClipboardFormatListener, SetClipboardData,
+            SetForegroundWindow, TranslateMessage, CF_BITMAP, CF_DIB, CF_DIBV5, CF_DSPBITMAP,
+            CF_DSPENHMETAFILE, CF_ENHMETAFILE, CF_HDROP, CF_METAFILEPICT, CF_OWNERDISPLAY,
+            CF_PALETTE, CF_UNICODETEXT, HWND_MESSAGE, MSG, WM_APP, WM_CLIPBOARDUPDATE, WNDCLASSW,
+        },
+    },
+};
+
+/// How long the target gets to read the clipboard before we restore it.
+const PASTE_TIMEOUT: Duration = Duration::from_millis(750);
+/// Grace period for targets that read the clipboard without us noticing.
+const PASTE_MIN_WAIT: Duration = Duration::from_millis(150);
+/// Posted to the listener window for every `WM_CLIPBOARDUPDATE`.
+const CLIPBOARD_CHANGED: UINT = WM_APP + 1;
+
+/// Clipboard sequence number right after our last restore, which [`watch`]
+/// skips so putting the user's clipboard back doesn't count as a new copy.
+static RESTORED_SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// Formats whose data is, or holds, a GDI handle rather than plain global
+/// memory. They can't be copied byte for byte; `CF_METAFILEPICT` is copied
+/// through its metafile, and Windows re-synthesizes most of the others from
+/// the DIB and metafile formats we keep.
+const HANDLE_FORMATS: [UINT; 7] = [
+    CF_BITMAP,
+    CF_PALETTE,
+    CF_METAFILEPICT,
+    CF_ENHMETAFILE,
+    CF_OWNERDISPLAY,
+    CF_DSPBITMAP,
+    CF_DSPENHMETAFILE,
+];
+
+pub fn register_format(name: &str) -> UINT {
+    let wide: Vec<u16> = OsStr::new(name)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe { RegisterClipboardFormatW(wide.as_ptr()) }
+}
+
+/// Keeps the clipboard open for as long as it lives.
+pub struct OpenedClipboard;
+
+impl OpenedClipboard {
+    /// Opens the clipboard, retrying briefly since other apps hold it for short moments.
+    pub fn open() -> Result<Self, QuackError> {
+        for _ in 0..10 {
+            if unsafe { OpenClipboard(ptr::null_mut()) } != 0 {
+                return Ok(OpenedClipboard);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Err(QuackError::clipboard("Failed to open the clipboard"))
+    }
+
+    /// Copies the contents of a global-memory format.
+    pub fn read_bytes(&self, format: UINT) -> Option<Vec<u8>> {
+        unsafe {
+            let handle = GetClipboardData(format);
+            if handle.is_null() {
+                return None;
+            }
+            let size = GlobalSize(handle);
+            let locked = GlobalLock(handle) as *const u8;
+            if locked.is_null() {
+                return None;
+            }
+            let bytes = std::slice::from_raw_parts(locked, size).to_vec();
+            GlobalUnlock(handle);
+            Some(bytes)
+        }
+    }
+
+    /// The `CF_UNICODETEXT` contents, up to the terminating NUL.
+    pub fn read_text(&self) -> Option<String> {
+        let bytes = self.read_bytes(CF_UNICODETEXT)?;
+        let wide: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        Some(String::from_utf16_lossy(&wide))
+    }
+
+    /// The `CF_METAFILEPICT` picture, with a copy of its metafile that stays
+    /// valid after the clipboard is emptied. The caller owns the copy.
+    pub fn read_metafile_pict(&self) -> Option<METAFILEPICT> {
+        let bytes = self.read_bytes(CF_METAFILEPICT)?;
+        if bytes.len() < mem::size_of::<METAFILEPICT>() {
+            return None;
+        }
+        unsafe {
+            let mut pict: METAFILEPICT = ptr::read_unaligned(bytes.as_ptr() as *const METAFILEPICT);
+            pict.hMF = CopyMetaFileW(pict.hMF, ptr::null());
+            (!pict.hMF.is_null()).then_some(pict)
+        }
+    }
+
+    /// Puts `pict` on the clipboard with a fresh copy of its metafile, which
+    /// the clipboard then owns.
+    pub fn write_metafile_pict(&self, pict: &METAFILEPICT) -> Result<(), QuackError> {
+        unsafe {
+            let copy = METAFILEPICT {
+                hMF: CopyMetaFileW(pict.hMF, ptr::null()),
+                ..*pict
+            };
+            if copy.hMF.is_null() {
+                return Err(QuackError::clipboard(
+                    "Failed to copy the clipboard metafile",
+                ));
+            }
+            let bytes = std::slice::from_raw_parts(
+                &copy as *const METAFILEPICT as *const u8,
+                mem::size_of::<METAFILEPICT>(),
+            );
+            self.write_bytes(CF_METAFILEPICT, bytes).inspect_err(|_| {
+                DeleteMetaFile(copy.hMF);
+            })
+        }
+    }
+
+    /// Puts a copy of `bytes` on the clipboard under `format`.
+    pub fn write_bytes(&self, format: UINT, bytes: &[u8]) -> Result<(), QuackError> {
+        unsafe {
+            let handle: HANDLE = GlobalAlloc(GMEM_MOVEABLE, bytes.len().max(1));
+            if handle.is_null() {
+                return Err(QuackError::clipboard("Failed to allocate clipboard memory"));
+            }
+            let locked = GlobalLock(handle) as *mut u8;
+            if locked.is_null() {
+                GlobalFree(handle);
+                return Err(QuackError::clipboard("Failed to lock clipboard memory"));
+            }
+            ptr::copy_nonoverlapping(bytes.as_ptr(), locked, bytes.len());
+            GlobalUnlock(handle);
+            // On success the clipboard owns the memory.
+            if SetClipboardData(format, handle).is_null() {
+                GlobalFree(handle);
+                return Err(QuackError::ClipboardFailed {
+                    message: format!("Failed to set clipboard format {}", format),
+                });
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) {
+        unsafe { CloseClipboard() };
+    }
+}
+
+/// One format saved in a [`ClipboardSnapshot`].
+enum SavedFormat {
+    Bytes(UINT, Vec<u8>),
+    /// Owns the metafile its `hMF` points to.
+    MetafilePict(METAFILEPICT),
+}
+
+/// Copy of every global-memory format on the clipboard, plus its metafile
+/// picture.
+pub struct ClipboardSnapshot {
+    formats: Vec<SavedFormat>,
+}
+
+impl ClipboardSnapshot {
+    pub fn take() -> Result<Self, QuackError> {
+        let clipboard = OpenedClipboard::open()?;
+        let mut formats = Vec::new();
+        let mut format = 0;
+        loop {
+            format = unsafe { EnumClipboardFormats(format) };
+            if format == 0 {
+                break;
+            }
+            if format == CF_METAFILEPICT {
+                if let Some(pict) = clipboard.read_metafile_pict() {
+                    formats.push(SavedFormat::MetafilePict(pict));
+                }
+                continue;
+            }
+            if HANDLE_FORMATS.contains(&format) {
+                continue;
+            }
+            if let Some(bytes) = clipboard.read_bytes(format) {
+                formats.push(SavedFormat::Bytes(format, bytes));
+            }
+        }
+        Ok(ClipboardSnapshot { formats })
+    }
+
+    /// Puts the saved formats back unchanged, so other clipboard monitors see
+    /// the user's copy as it was.
+    pub fn restore(&self) -> Result<(), QuackError> {
+        let clipboard = OpenedClipboard::open()?;
+        unsafe { EmptyClipboard() };
+        for saved in &self.formats {
+            // Keep going so one odd private format doesn't lose the rest.
+            let _ = match saved {
+                SavedFormat::Bytes(format, bytes) => clipboard.write_bytes(*format, bytes),
+                SavedFormat::MetafilePict(pict) => clipboard.write_metafile_pict(pict),
+            };
+        }
+        // Recorded while the clipboard is still open, before the update
+        // notification goes out.
+        RESTORED_SEQUENCE.store(unsafe { GetClipboardSequenceNumber() }, Ordering::Relaxed);
+        drop(clipboard);
+        Ok(())
+    }
+}
+
+impl Drop for ClipboardSnapshot {
+    fn drop(&mut self) {
+        for saved in &self.formats {
+            if let SavedFormat::MetafilePict(pict) = saved {
+                unsafe { DeleteMetaFile(pict.hMF) };
+            }
+        }
+    }
+}
+
+/// Wraps an HTML fragment in the `CF_HTML` ("HTML Format") header.
+pub fn cf_html(fragment: &str) -> String {
+    const HEADER: &str = "Version:0.9\r\nStartHTML:{sh}\r\nEndHTML:{eh}\r\nStartFragment:{sf}\r\nEndFragment:{ef}\r\n";
+    const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+    // The offsets are written as fixed-width numbers so the header length is known up front.
+    let header_len = HEADER.len() - "{sh}{eh}{sf}{ef}".len() + 4 * 10;
+    let start_html = header_len;
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+    let header = HEADER
+        .replace("{sh}", &format!("{:010}", start_html))
+        .replace("{eh}", &format!("{:010}", end_html))
+        .replace("{sf}", &format!("{:010}", start_fragment))
+        .replace("{ef}", &format!("{:010}", end_fragment));
+    format!("{}{}{}{}", header, PREFIX, fragment, SUFFIX)
+}
+
+/// Splits `CF_HTML` data into the fragment and the `SourceURL` from its header.
+fn parse_cf_html(data: &[u8]) -> Option<(String, Option<String>)> {
+    let (mut start, mut end, mut source_url) = (None, None, None);
+    for line in data.split(|&b| b == b'\n') {
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with('<') {
+            break;
+        }
+        let Some((key, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        match key {
+            "StartFragment" => start = value.parse::<usize>().ok(),
+            "EndFragment" => end = value.parse::<usize>().ok(),
+            "SourceURL" => source_url = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    let fragment = data.get(start?..end?)?;
+    Some((String::from_utf8_lossy(fragment).into_owned(), source_url))
+}
+
+/// The paths in a `CF_HDROP` block: a `DROPFILES` header followed by a
+/// double-NUL-terminated list of NUL-terminated paths.
+fn parse_hdrop(data: &[u8]) -> Option<Vec<PathBuf>> {
+    let dword = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    let list = data.get(dword(0)? as usize..)?;
+    let paths: Vec<PathBuf> = if dword(16)? != 0 {
+        let wide: Vec<u16> = list
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        wide.split(|&unit| unit == 0)
+            .take_while(|path| !path.is_empty())
+            .map(|path| OsString::from_wide(path).into())
+            .collect()
+    } else {
+        list.split(|&b| b == 0)
+            .take_while(|path| !path.is_empty())
+            .map(|path| String::from_utf8_lossy(path).into_owned().into())
+            .collect()
+    };
+    (!paths.is_empty()).then_some(paths)
+}
+
+/// Decodes a `CF_DIB` or `CF_DIBV5` bitmap, which is a BMP file without the
+/// file header.
+fn decode_dib(data: Vec<u8>) -> Option<RgbaImage> {
+    let decoder = BmpDecoder::new_without_file_header(io::Cursor::new(data)).ok()?;
+    Some(DynamicImage::from_decoder(decoder).ok()?.to_rgba8())
+}
+
+/// Reads the clipboard in the richest format we understand. Files win; text
+/// comes as HTML or RTF when offered; a bitmap only counts when there's no
+/// text, since Office also puts a picture of copied text on the clipboard.
+fn read_content(clipboard: &OpenedClipboard) -> Option<ClipContent> {
+    if let Some(paths) = clipboard
+        .read_bytes(CF_HDROP)
+        .and_then(|data| parse_hdrop(&data))
+    {
+        return Some(ClipContent::Files(paths));
+    }
+    let text = clipboard.read_text();
+    let html = clipboard
+        .read_bytes(register_format("HTML Format"))
+        .and_then(|data| parse_cf_html(&data));
+    if text.is_some() {
+        if let Some((fragment, source_url)) = html {
+            return Some(ClipContent::Html {
+                fragment,
+                source_url,
+                text,
+            });
+        }
+        if let Some(rtf) = clipboard.read_bytes(register_format("Rich Text Format")) {
+            let rtf = String::from_utf8_lossy(&rtf)
+                .trim_end_matches('\0')
+                .to_string();
+            return Some(ClipContent::Rtf { rtf, text });
+        }
+        return text.map(ClipContent::Text);
+    }
+    let image = clipboard
+        .read_bytes(CF_DIBV5)
+        .or_else(|| clipboard.read_bytes(CF_DIB))
+        .and_then(decode_dib);
+    if let Some(image) = image {
+        return Some(ClipContent::Image(image));
+    }
+    html.map(|(fragment, source_url)| ClipContent::Html {
+        fragment,
+        source_url,
+        text: None,
+    })
+}
+
+/// Writes text (plus optional HTML / RTF) to the clipboard, tagged so that
+/// clipboard monitors and Windows' clipboard history ignore it.
+fn write_paste_content(content: &PasteContent) -> Result<(), QuackError> {
+    let clipboard = OpenedClipboard::open()?;
+    unsafe { EmptyClipboard() };
+
+    let wide: Vec<u16> = OsStr::new(content.text)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let bytes: Vec<u8> = wide.iter().flat_map(|u| u.to_le_bytes()).collect();
+    clipboard.write_bytes(CF_UNICODETEXT, &bytes)?;
+
+    if let Some(html) = content.html {
+        let mut data = cf_html(html).into_bytes();
+        data.push(0);
+        clipboard.write_bytes(register_format("HTML Format"), &data)?;
+    }
+    if let Some(rtf) = content.rtf {
+        let mut data = rtf.as_bytes().to_vec();
+        data.push(0);
+        clipboard.write_bytes(register_format("Rich Text Format"), &data)?;
+    }
+
+    let _ = clipboard.write_bytes(
+        register_format("ExcludeClipboardContentFromMonitorProcessing"),
+        &0u32.to_le_bytes(),
+    );
+    let _ = clipboard.write_bytes(
+        register_format("CanIncludeInClipboardHistory"),
+        &0u32.to_le_bytes(),
+    );
+    Ok(())
+}
+
+/// Waits until the target has opened and closed the clipboard, or the timeout passes.
+fn wait_for_paste_consumed() {
+    let started = std::time::Instant::now();
+    let mut seen_open = false;
+    while started.elapsed() < PASTE_TIMEOUT {
+        let is_open = unsafe { !GetOpenClipboardWindow().is_null() };
+        if is_open {
+            seen_open = true;
+        } else if seen_open && started.elapsed() >= PASTE_MIN_WAIT {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Puts a snapshot back on the clipboard when dropped, so a paste restores
+/// the user's clipboard however it ends.
+struct RestoreOnDrop(ClipboardSnapshot);
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.restore() {
+            eprintln!("Failed to restore the clipboard after pasting: {}", e);
+        }
+    }
+}
+
+/// Pastes `content` into `hwnd` with Ctrl+V and puts the user's clipboard back.
+///
+/// Fails only before Ctrl+V is sent. Clipboard errors come back as
+/// `ClipboardFailed`; failing to restore the user's clipboard afterwards is
+/// logged rather than returned, since the text was pasted by then.
+pub fn paste_to_window(hwnd: HWND, content: &PasteContent) -> Result<(), QuackError> {
+    let _restore = RestoreOnDrop(ClipboardSnapshot::take()?);
+    write_paste_content(content)?;
+
+    if unsafe { SetForegroundWindow(hwnd) } == 0 {
+        return Err(QuackError::access_denied(
+            "Failed to bring target window to foreground",
+        ));
+    }
+    std::thread::sleep(Duration::from_millis(50));
+    send_input_events(&plan_chord(NamedKey::Control, NamedKey::Letter('V')))?;
+    wait_for_paste_consumed();
+    Ok(())
+}
+
+/// Window procedure of the listener window. `WM_CLIPBOARDUPDATE` may arrive
+/// as a sent message, which `GetMessageW` never hands back, so it is turned
+/// into a posted one for the loop in [`watch`].
+unsafe extern "system" fn listener_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_CLIPBOARDUPDATE {
+        PostMessageW(hwnd, CLIPBOARD_CHANGED, 0, 0);
+        return 0;
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Creates an invisible message-only window to receive clipboard updates.
+fn create_listener_window() -> Result<HWND, QuackError> {
+    let class_name: Vec<u16> = OsStr::new("QuackClipboardListener")
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(listener_proc),
+            hInstance: instance,
+            lpszClassName: class_name.as_ptr(),
+            ..mem::zeroed()
+        };
+        // Fails with ERROR_CLASS_ALREADY_EXISTS when watching a second time,
+        // which is fine.
+        RegisterClassW(&class);
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            ptr::null_mut(),
+            instance,
+            ptr::null_mut(),
+        );
+        if hwnd.is_null() {
+            return Err(QuackError::os(
+                "Failed to create the clipboard listener window",
+            ));
+        }
+        Ok(hwnd)
+    }
+}
+
+/// Blocks in a message loop and reports every clipboard change until
+/// `on_change` returns `false`.
+pub fn watch(on_change: &mut dyn FnMut(ClipboardChange) -> bool) -> Result<(), QuackError> {
+    let exclude_format = register_format("ExcludeClipboardContentFromMonitorProcessing");
+    let history_format = register_format("CanIncludeInClipboardHistory");
+    let hwnd = create_listener_window()?;
+    unsafe {
+        if AddClipboardFormatListener(hwnd) == 0 {
+            DestroyWindow(hwnd);
+            return Err(QuackError::os("Failed to listen for clipboard changes"));
+        }
+        let mut msg: MSG = mem::zeroed();
+        while GetMessageW(&mut msg, hwnd, 0, 0) > 0 {
+            if msg.message != CLIPBOARD_CHANGED {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+                continue;
+            }
+            // Skip content that asks not to be monitored, including the
+            // temporary clipboard contents of our own paste injection.
+            if IsClipboardFormatAvailable(exclude_format) != 0 {
+                continue;
+            }
+            // Our restore after a paste, unless something was copied since.
+            if GetClipboardSequenceNumber() == RESTORED_SEQUENCE.load(Ordering::Relaxed) {
+                continue;
+            }
+            let Ok(clipboard) = OpenedClipboard::open() else {
+                continue;
+            };
+            let change = ClipboardChange {
+                content: read_content(&clipboard),
+                exclude_from_history: clipboard
+                    .read_bytes(history_format)
+                    .is_some_and(|bytes| bytes.starts_with(&0u32.to_le_bytes())),
+            };
+            // Let go of the clipboard before handing the change on.
+            drop(clipboard);
+            if !on_change(change) {
+                break;
+            }
+        }
+        RemoveClipboardFormatListener(hwnd);
+        DestroyWindow(hwnd);
+    }
+    Ok(())
+}
@@ -0,0 +1,251 @@
+//! Drop guards for the GDI objects used by screen and window capture and icon
+//! conversion, and the capture pipeline built on them.
+//!
+//! Every handle is released when its guard goes out of scope, so early
+//! returns can't leak DCs or bitmaps. Guards are declared in creation order
+//! and therefore dropped in reverse: the selection is undone before the
+//! bitmap and memory DC are deleted, and those before the source DC is
+//! released.
+
+use crate::error::QuackError;
+use image::RgbaImage;
+use std::{mem, ptr};
+use winapi::{
+    ctypes::c_void,
+    shared::windef::{HBITMAP, HDC, HGDIOBJ, HWND},
+    um::{
+        wingdi::{
+            CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDIBits,
+            GetObjectW, SelectObject, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+        },
+        winuser::{GetDC, ReleaseDC},
+    },
+};
+
+/// A DC obtained with `GetDC`, for a window or (with a null `HWND`) the screen.
+pub struct WindowDc {
+    hwnd: HWND,
+    hdc: HDC,
+}
+
+impl WindowDc {
+    pub fn get(hwnd: HWND) -> Result<Self, QuackError> {
+        let hdc = unsafe { GetDC(hwnd) };
+        if hdc.is_null() {
+            return Err(QuackError::capture("GetDC"));
+        }
+        Ok(WindowDc { hwnd, hdc })
+    }
+
+    /// The DC of the whole virtual screen.
+    pub fn screen() -> Result<Self, QuackError> {
+        Self::get(ptr::null_mut())
+    }
+
+    pub fn hdc(&self) -> HDC {
+        self.hdc
+    }
+}
+
+impl Drop for WindowDc {
+    fn drop(&mut self) {
+        unsafe { ReleaseDC(self.hwnd, self.hdc) };
+    }
+}
+
+/// A memory DC from `CreateCompatibleDC`.
+pub struct MemoryDc(HDC);
+
+impl MemoryDc {
+    pub fn compatible_with(dc: &WindowDc) -> Result<Self, QuackError> {
+        let hdc = unsafe { CreateCompatibleDC(dc.hdc) };
+        if hdc.is_null() {
+            return Err(QuackError::capture("CreateCompatibleDC"));
+        }
+        Ok(MemoryDc(hdc))
+    }
+
+    pub fn hdc(&self) -> HDC {
+        self.0
+    }
+
+    /// Selects `bitmap` into this DC until the returned guard is dropped.
+    pub fn select<'a>(&'a self, bitmap: &'a Bitmap) -> Selection<'a> {
+        let previous = unsafe { SelectObject(self.0, bitmap.0 as HGDIOBJ) };
+        Selection { dc: self, previous }
+    }
+}
+
+impl Drop for MemoryDc {
+    fn drop(&mut self) {
+        unsafe { DeleteDC(self.0) };
+    }
+}
+
+/// A bitmap we own, e.g. from `CreateCompatibleBitmap` or `GetIconInfo`.
+pub struct Bitmap(HBITMAP);
+
+impl Bitmap {
+    pub fn compatible_with(dc: &WindowDc, width: i32, height: i32) -> Result<Self, QuackError> {
+        let bitmap = unsafe { CreateCompatibleBitmap(dc.hdc, width, height) };
+        if bitmap.is_null() {
+            return Err(QuackError::capture("CreateCompatibleBitmap"));
+        }
+        Ok(Bitmap(bitmap))
+    }
+
+    /// Takes ownership of `bitmap`, or `None` if it's null.
+    pub fn from_raw(bitmap: HBITMAP) -> Option<Self> {
+        (!bitmap.is_null()).then_some(Bitmap(bitmap))
+    }
+
+    /// Copies the pixels out as RGBA. The bitmap must not be selected into a
+    /// DC at this point.
+    pub fn to_rgba(&self) -> Result<RgbaImage, QuackError> {
+        let mut info: BITMAP = unsafe { mem::zeroed() };
+        let size = mem::size_of::<BITMAP>() as i32;
+        if unsafe { GetObjectW(self.0 as HGDIOBJ, size, &mut info as *mut _ as *mut c_void) } == 0 {
+            return Err(QuackError::capture("GetObjectW"));
+        }
+        if info.bmWidth <= 0 || info.bmHeight <= 0 {
+            return Err(QuackError::capture("bitmap size"));
+        }
+        let screen = WindowDc::screen()?;
+        read_bitmap(screen.hdc, self, info.bmWidth, info.bmHeight)
+    }
+}
+
+impl Drop for Bitmap {
+    fn drop(&mut self) {
+        unsafe { DeleteObject(self.0 as HGDIOBJ) };
+    }
+}
+
+/// Puts the previously selected object back into the DC when dropped.
+pub struct Selection<'a> {
+    dc: &'a MemoryDc,
+    previous: HGDIOBJ,
+}
+
+impl Drop for Selection<'_> {
+    fn drop(&mut self) {
+        unsafe { SelectObject(self.dc.0, self.previous) };
+    }
+}
+
+/// Copies the bitmap's pixels out as RGBA through `hdc`. The bitmap must not
+/// be selected into a DC at this point.
+fn read_bitmap(
+    hdc: HDC,
+    bitmap: &Bitmap,
+    width: i32,
+    height: i32,
+) -> Result<RgbaImage, QuackError> {
+    let mut bitmap_info: BITMAPINFO = unsafe { mem::zeroed() };
+    bitmap_info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+    bitmap_info.bmiHeader.biWidth = width;
+    bitmap_info.bmiHeader.biHeight = -height; // Negative for top-down
+    bitmap_info.bmiHeader.biPlanes = 1;
+    bitmap_info.bmiHeader.biBitCount = 32;
+    bitmap_info.bmiHeader.biCompression = BI_RGB;
+
+    let mut buffer: Vec<u8> = vec![0; (width * height * 4) as usize];
+    let lines = unsafe {
+        GetDIBits(
+            hdc,
+            bitmap.0,
+            0,
+            height as u32,
+            buffer.as_mut_ptr() as *mut c_void,
+            &mut bitmap_info,
+            DIB_RGB_COLORS,
+        )
+    };
+    if lines == 0 {
+        return Err(QuackError::capture("GetDIBits"));
+    }
+
+    // Convert BGRA to RGBA
+    for chunk in buffer.chunks_exact_mut(4) {
+        chunk.swap(0, 2);
+    }
+    RgbaImage::from_raw(width as u32, height as u32, buffer)
+        .ok_or_else(|| QuackError::capture("image buffer"))
+}
+
+/// The capture pipeline shared by screen, region and window capture.
+///
+/// Creates a `width`×`height` bitmap compatible with `source`, lets `render`
+/// draw into it through a memory DC, and reads the result back as RGBA.
+pub fn capture_with(
+    source: &WindowDc,
+    width: i32,
+    height: i32,
+    render: impl FnOnce(HDC) -> Result<(), QuackError>,
+) -> Result<RgbaImage, QuackError> {
+    if width <= 0 || height <= 0 {
+        return Err(QuackError::capture("capture size"));
+    }
+    let memory = MemoryDc::compatible_with(source)?;
+    let bitmap = Bitmap::compatible_with(source, width, height)?;
+    {
+        let _selection = memory.select(&bitmap);
+        render(memory.hdc())?;
+    }
+    read_bitmap(memory.hdc(), &bitmap, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use winapi::um::winuser::{LoadIconW, IDI_APPLICATION};
+    use windows::Win32::System::Threading::{GetCurrentProcess, GetGuiResources, GR_GDIOBJECTS};
+
+    const ITERATIONS: usize = 10_000;
+
+    /// GDI object counts are per process, so the leak checks can't overlap.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn gdi_objects() -> u32 {
+        unsafe { GetGuiResources(GetCurrentProcess(), GR_GDIOBJECTS) }
+    }
+
+    /// Runs `f` many times and checks the process's GDI object count stays
+    /// flat. The first run may allocate objects GDI keeps around for good.
+    fn assert_no_gdi_leak(mut f: impl FnMut()) {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        f();
+        let before = gdi_objects();
+        for _ in 0..ITERATIONS {
+            f();
+        }
+        assert_eq!(gdi_objects(), before);
+    }
+
+    #[test]
+    fn screen_capture_does_not_leak() {
+        assert_no_gdi_leak(|| {
+            let image = super::super::capture_screen_region(0, 0, 64, 64).expect("capture");
+            assert_eq!(image.dimensions(), (64, 64));
+        });
+    }
+
+    #[test]
+    fn failed_capture_does_not_leak() {
+        assert_no_gdi_leak(|| {
+            let screen = WindowDc::screen().expect("screen DC");
+            let result = capture_with(&screen, 64, 64, |_| Err(QuackError::capture("test")));
+            assert!(result.is_err());
+        });
+    }
+
+    #[test]
+    fn icon_conversion_does_not_leak() {
+        let icon = unsafe { LoadIconW(ptr::null_mut(), IDI_APPLICATION) };
+        assert!(!icon.is_null());
+        assert_no_gdi_leak(|| {
+            assert!(super::super::hicon_to_base64_png(icon).is_some());
+        });
+    }
+}
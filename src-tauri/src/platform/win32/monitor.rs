@@ -0,0 +1,191 @@
+//! Monitor enumeration and lookup for multi-monitor capture.
+//!
+//! All coordinates are physical pixels. Callers wrap their work in a
+//! [`PhysicalDpiScope`] so that holds even if the process itself isn't
+//! per-monitor DPI aware.
+
+use crate::error::QuackError;
+use crate::platform::{MonitorInfo, WindowBounds};
+use std::{ffi::OsString, mem, os::windows::ffi::OsStringExt, ptr};
+use winapi::{
+    shared::{
+        minwindef::{BOOL, LPARAM, TRUE},
+        windef::{
+            DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, HDC, HMONITOR, HWND,
+            LPRECT, POINT,
+        },
+        winerror::S_OK,
+    },
+    um::{
+        shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
+        winuser::{
+            EnumDisplayMonitors, GetCursorPos, GetMonitorInfoW, GetSystemMetrics, MonitorFromPoint,
+            MonitorFromWindow, SetThreadDpiAwarenessContext, MONITORINFO, MONITORINFOEXW,
+            MONITORINFOF_PRIMARY, MONITOR_DEFAULTTONEAREST, MONITOR_DEFAULTTONULL,
+            SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+        },
+    },
+};
+
+/// DPI at 100% display scaling.
+const BASE_DPI: f64 = 96.0;
+
+/// Makes the calling thread per-monitor DPI aware until dropped.
+pub struct PhysicalDpiScope(DPI_AWARENESS_CONTEXT);
+
+impl PhysicalDpiScope {
+    pub fn enter() -> Self {
+        PhysicalDpiScope(unsafe {
+            SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2)
+        })
+    }
+}
+
+impl Drop for PhysicalDpiScope {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { SetThreadDpiAwarenessContext(self.0) };
+        }
+    }
+}
+
+/// A monitor handle together with what [`MonitorInfo`] reports about it.
+struct Monitor {
+    handle: HMONITOR,
+    info: MonitorInfo,
+}
+
+fn describe(handle: HMONITOR) -> Option<Monitor> {
+    let mut info: MONITORINFOEXW = unsafe { mem::zeroed() };
+    info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+    if unsafe { GetMonitorInfoW(handle, &mut info as *mut _ as *mut MONITORINFO) } == 0 {
+        return None;
+    }
+
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    let scale_factor =
+        if unsafe { GetDpiForMonitor(handle, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) } == S_OK {
+            dpi_x as f64 / BASE_DPI
+        } else {
+            1.0
+        };
+
+    let len = info
+        .szDevice
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info.szDevice.len());
+    let rect = info.rcMonitor;
+    Some(Monitor {
+        handle,
+        info: MonitorInfo {
+            index: 0,
+            name: OsString::from_wide(&info.szDevice[..len])
+                .to_string_lossy()
+                .into_owned(),
+            bounds: WindowBounds {
+                x: rect.left,
+                y: rect.top,
+                width: (rect.right - rect.left).max(0) as u32,
+                height: (rect.bottom - rect.top).max(0) as u32,
+            },
+            scale_factor,
+            primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+        },
+    })
+}
+
+unsafe extern "system" fn collect_monitor(
+    handle: HMONITOR,
+    _dc: HDC,
+    _rect: LPRECT,
+    lparam: LPARAM,
+) -> BOOL {
+    let handles = &mut *(lparam as *mut Vec<HMONITOR>);
+    handles.push(handle);
+    TRUE
+}
+
+/// Connected monitors, the primary one first and the rest left to right.
+fn enumerate() -> Result<Vec<Monitor>, QuackError> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    let ok = unsafe {
+        EnumDisplayMonitors(
+            ptr::null_mut(),
+            ptr::null(),
+            Some(collect_monitor),
+            &mut handles as *mut Vec<HMONITOR> as LPARAM,
+        )
+    };
+    if ok == 0 {
+        return Err(QuackError::capture("EnumDisplayMonitors"));
+    }
+
+    let mut monitors: Vec<Monitor> = handles.into_iter().filter_map(describe).collect();
+    monitors.sort_by_key(|m| (!m.info.primary, m.info.bounds.x, m.info.bounds.y));
+    for (index, monitor) in monitors.iter_mut().enumerate() {
+        monitor.info.index = index;
+    }
+    Ok(monitors)
+}
+
+pub fn monitors() -> Result<Vec<MonitorInfo>, QuackError> {
+    Ok(enumerate()?.into_iter().map(|m| m.info).collect())
+}
+
+/// The monitor whose handle is `handle`, with its index filled in.
+fn find(handle: HMONITOR) -> Result<MonitorInfo, QuackError> {
+    enumerate()?
+        .into_iter()
+        .find(|m| m.handle == handle)
+        .map(|m| m.info)
+        .ok_or_else(|| QuackError::capture("monitor lookup"))
+}
+
+pub fn at_index(index: usize) -> Result<MonitorInfo, QuackError> {
+    enumerate()?
+        .into_iter()
+        .nth(index)
+        .map(|m| m.info)
+        .ok_or_else(|| QuackError::invalid_argument(format!("No monitor at index {}", index)))
+}
+
+pub fn under_cursor() -> Result<MonitorInfo, QuackError> {
+    let mut point = POINT { x: 0, y: 0 };
+    if unsafe { GetCursorPos(&mut point) } == 0 {
+        return Err(QuackError::capture("GetCursorPos"));
+    }
+    find(unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST) })
+}
+
+/// The bounding box of all monitors.
+pub fn virtual_desktop() -> Result<WindowBounds, QuackError> {
+    let (x, y, width, height) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+    if width <= 0 || height <= 0 {
+        return Err(QuackError::capture("GetSystemMetrics"));
+    }
+    Ok(WindowBounds {
+        x,
+        y,
+        width: width as u32,
+        height: height as u32,
+    })
+}
+
+/// The monitor that shows the largest part of `hwnd`.
+pub fn of_window(hwnd: HWND) -> Result<MonitorInfo, QuackError> {
+    let handle = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL) };
+    if handle.is_null() {
+        return Err(QuackError::invalid_argument(
+            "The window is not on any monitor",
+        ));
+    }
+    find(handle)
+}
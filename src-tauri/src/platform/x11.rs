@@ -0,0 +1,727 @@
+//! This module contains the Linux (X11) backend built on the x11rb crate.
+//! Window information comes from EWMH properties set by the window manager:
+//! `_NET_ACTIVE_WINDOW`, `_NET_CLIENT_LIST`, `_NET_WM_NAME`, `_NET_WM_PID` and
+//! `_NET_WM_ICON`. Text is typed through the XTEST extension (see [`input`]),
+//! screenshots are taken with `GetImage` and Composite (see [`capture`]),
+//! clipboard changes come from XFixes and pasting owns the `CLIPBOARD`
+//! selection (see [`clipboard`]).
+
+use super::{
+    freedesktop, ClipboardChange, ClipboardWatcher, MonitorInfo, NativeWindow, Platform,
+    ScreenCapture, ScreenTarget, WindowBounds, WindowId,
+};
+use crate::error::QuackError;
+use crate::injection::{
+    plan::{plan_text, InputEvent},
+    PasteContent,
+};
+use base64::{engine::general_purpose, Engine as _};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder, RgbaImage};
+use std::{os::unix::fs::MetadataExt, path::PathBuf};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        screensaver::{self, ConnectionExt as _},
+        xproto::{
+            AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _, EventMask,
+            Window,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+pub mod capture;
+pub mod clipboard;
+pub mod input;
+
+/// Edge length in pixels of the icons we hand to the frontend.
+const ICON_SIZE: u32 = 32;
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_CURRENT_DESKTOP,
+        _NET_WM_DESKTOP,
+        _NET_WM_STATE,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_ICON,
+        UTF8_STRING,
+    }
+}
+
+/// An open X11 connection plus the atoms we look up on every call.
+pub struct X11Connection {
+    pub conn: RustConnection,
+    pub screen_num: usize,
+    pub root: Window,
+    pub atoms: Atoms,
+}
+
+impl X11Connection {
+    /// Connects to the display named by `$DISPLAY`.
+    pub fn connect() -> Result<Self, QuackError> {
+        Self::connect_to(None)
+    }
+
+    /// Connects to an explicit display such as `":99"` (e.g. an Xvfb server).
+    pub fn connect_to(display: Option<&str>) -> Result<Self, QuackError> {
+        let (conn, screen_num) = x11rb::connect(display)
+            .map_err(|e| QuackError::os(format!("Failed to connect to X server: {}", e)))?;
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        Ok(X11Connection {
+            conn,
+            screen_num,
+            root,
+            atoms,
+        })
+    }
+
+    /// Reads a 32-bit-format property as a list of values.
+    pub fn get_u32_property(
+        &self,
+        window: Window,
+        property: u32,
+        ty: impl Into<u32>,
+        max_len: u32,
+    ) -> Option<Vec<u32>> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, ty, 0, max_len)
+            .ok()?
+            .reply()
+            .ok()?;
+        let values = reply.value32()?.collect();
+        Some(values)
+    }
+
+    /// The currently focused client window according to `_NET_ACTIVE_WINDOW`.
+    pub fn active_window(&self) -> Option<Window> {
+        self.get_u32_property(
+            self.root,
+            self.atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            1,
+        )?
+        .first()
+        .copied()
+        .filter(|&w| w != 0)
+    }
+
+    /// All managed top-level windows in mapping order.
+    pub fn client_list(&self) -> Vec<Window> {
+        self.get_u32_property(
+            self.root,
+            self.atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            u32::MAX,
+        )
+        .unwrap_or_default()
+    }
+
+    /// Managed windows from `_NET_CLIENT_LIST_STACKING`, topmost first.
+    pub fn stacking_order(&self) -> Vec<Window> {
+        let mut windows = self
+            .get_u32_property(
+                self.root,
+                self.atoms._NET_CLIENT_LIST_STACKING,
+                AtomEnum::WINDOW,
+                u32::MAX,
+            )
+            .unwrap_or_else(|| self.client_list());
+        // EWMH lists bottom-to-top.
+        windows.reverse();
+        windows
+    }
+
+    /// Position (relative to the root window) and size of a window.
+    pub fn window_bounds(&self, window: Window) -> Option<WindowBounds> {
+        let geometry = self.conn.get_geometry(window).ok()?.reply().ok()?;
+        let origin = self
+            .conn
+            .translate_coordinates(window, self.root, 0, 0)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(WindowBounds {
+            x: origin.dst_x as i32,
+            y: origin.dst_y as i32,
+            width: geometry.width as u32,
+            height: geometry.height as u32,
+        })
+    }
+
+    /// Whether the window lives on a virtual desktop other than the current one.
+    fn on_other_desktop(&self, window: Window) -> bool {
+        let desktop = |w: Window, atom: u32| {
+            self.get_u32_property(w, atom, AtomEnum::CARDINAL, 1)?
+                .first()
+                .copied()
+        };
+        match (
+            desktop(window, self.atoms._NET_WM_DESKTOP),
+            desktop(self.root, self.atoms._NET_CURRENT_DESKTOP),
+        ) {
+            // 0xFFFFFFFF means "sticky", i.e. shown on all desktops.
+            (Some(d), Some(current)) => d != 0xFFFF_FFFF && d != current,
+            _ => false,
+        }
+    }
+
+    /// Docks, toolbars, menus and windows that opted out of the taskbar.
+    fn is_tool_window(&self, window: Window) -> bool {
+        let types = self
+            .get_u32_property(window, self.atoms._NET_WM_WINDOW_TYPE, AtomEnum::ATOM, 32)
+            .unwrap_or_default();
+        let normal = [
+            self.atoms._NET_WM_WINDOW_TYPE_NORMAL,
+            self.atoms._NET_WM_WINDOW_TYPE_DIALOG,
+        ];
+        let special_type = !types.is_empty() && !types.iter().any(|t| normal.contains(t));
+        let skip_taskbar = self
+            .get_u32_property(window, self.atoms._NET_WM_STATE, AtomEnum::ATOM, 32)
+            .unwrap_or_default()
+            .contains(&self.atoms._NET_WM_STATE_SKIP_TASKBAR);
+        special_type || skip_taskbar
+    }
+
+    /// `_NET_WM_NAME` (UTF-8), falling back to the legacy `WM_NAME`.
+    pub fn window_title(&self, window: Window) -> String {
+        let read = |property: u32, ty: u32| {
+            let reply = self
+                .conn
+                .get_property(false, window, property, ty, 0, u32::MAX)
+                .ok()?
+                .reply()
+                .ok()?;
+            (!reply.value.is_empty()).then(|| String::from_utf8_lossy(&reply.value).into_owned())
+        };
+        read(self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)
+            .or_else(|| read(AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+            .unwrap_or_default()
+    }
+
+    /// The owning process id as advertised by `_NET_WM_PID`.
+    pub fn window_pid(&self, window: Window) -> Option<u32> {
+        self.get_u32_property(window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL, 1)?
+            .first()
+            .copied()
+    }
+
+    /// Both parts of `WM_CLASS` (`"instance\0class\0"`).
+    pub fn wm_class(&self, window: Window) -> Option<(String, String)> {
+        let reply = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)
+            .ok()?
+            .reply()
+            .ok()?;
+        let mut parts = reply
+            .value
+            .split(|&b| b == 0)
+            .map(|p| String::from_utf8_lossy(p).into_owned());
+        let instance = parts.next().unwrap_or_default();
+        let class = parts.next().unwrap_or_default();
+        (!instance.is_empty() || !class.is_empty()).then_some((instance, class))
+    }
+
+    /// The class part of `WM_CLASS`, or the instance when the class is empty.
+    pub fn window_class(&self, window: Window) -> Option<String> {
+        let (instance, class) = self.wm_class(window)?;
+        Some(if class.is_empty() { instance } else { class })
+    }
+
+    /// Picks the `_NET_WM_ICON` entry closest to [`ICON_SIZE`] and returns it as RGBA.
+    pub fn window_icon(&self, window: Window) -> Option<RgbaImage> {
+        let data = self.get_u32_property(
+            window,
+            self.atoms._NET_WM_ICON,
+            AtomEnum::CARDINAL,
+            u32::MAX,
+        )?;
+        // The property is a sequence of [width, height, width * height ARGB pixels].
+        let mut best: Option<(u32, u32, &[u32])> = None;
+        let mut rest = data.as_slice();
+        while rest.len() >= 2 {
+            let (w, h) = (rest[0], rest[1]);
+            let len = (w as usize).checked_mul(h as usize)?;
+            if w == 0 || h == 0 || rest.len() < 2 + len {
+                break;
+            }
+            let pixels = &rest[2..2 + len];
+            let better = match best {
+                Some((bw, _, _)) => w.abs_diff(ICON_SIZE) < bw.abs_diff(ICON_SIZE),
+                None => true,
+            };
+            if better {
+                best = Some((w, h, pixels));
+            }
+            rest = &rest[2 + len..];
+        }
+        let (w, h, pixels) = best?;
+        let rgba = pixels
+            .iter()
+            .flat_map(|&argb| {
+                let [b, g, r, a] = argb.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+        RgbaImage::from_raw(w, h, rgba)
+    }
+
+    /// Asks the window manager to activate `window` via a `_NET_ACTIVE_WINDOW` client message.
+    pub fn activate_window(&self, window: Window) -> Result<(), QuackError> {
+        // Source indication 2 = pager, which window managers honour without focus-stealing checks.
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms._NET_ACTIVE_WINDOW,
+            [2, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        self.conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        Ok(self.conn.flush()?)
+    }
+
+    /// Whether the screensaver is on, which is when X11 screen lockers show.
+    /// `false` when the server lacks the MIT-SCREEN-SAVER extension.
+    pub fn screensaver_active(&self) -> bool {
+        self.conn
+            .screensaver_query_info(self.root)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.state == u8::from(screensaver::State::ON))
+    }
+
+    /// Whether the active window is an authentication prompt or a screen
+    /// locker running as a regular window.
+    pub fn auth_prompt_active(&self) -> bool {
+        self.active_window()
+            .and_then(|window| self.wm_class(window))
+            .is_some_and(|(instance, class)| is_auth_prompt_class(&instance, &class))
+    }
+}
+
+/// `WM_CLASS` fragments of polkit agents, keyring, pinentry and SSH prompts,
+/// and lockers that show up as the active window.
+const AUTH_PROMPT_CLASSES: [&str; 9] = [
+    "polkit",
+    "pinentry",
+    "askpass",
+    "gcr-prompter",
+    "xscreensaver",
+    "i3lock",
+    "xsecurelock",
+    "light-locker",
+    "slock",
+];
+
+fn is_auth_prompt_class(instance: &str, class: &str) -> bool {
+    [instance, class].iter().any(|name| {
+        let name = name.to_lowercase();
+        AUTH_PROMPT_CLASSES.iter().any(|word| name.contains(word))
+    })
+}
+
+/// Subscribes to `PropertyNotify` on the given window (or clears the subscription).
+fn select_property_changes(x: &X11Connection, window: Window, enabled: bool) {
+    let mask = if enabled {
+        EventMask::PROPERTY_CHANGE
+    } else {
+        EventMask::NO_EVENT
+    };
+    let _ = x
+        .conn
+        .change_window_attributes(window, &ChangeWindowAttributesAux::new().event_mask(mask));
+}
+
+/// Blocks on `x`, calling `on_change` with the focused window and its title
+/// whenever either changes. Called once up front with the initial state.
+///
+/// Listens for `PropertyNotify` on `_NET_ACTIVE_WINDOW` on the root window and
+/// on `_NET_WM_NAME` / `WM_NAME` on the focused window, so nothing is emitted
+/// while focus and title stay put. Only returns if the connection breaks.
+pub fn watch_active_window(
+    x: &X11Connection,
+    on_change: &mut dyn FnMut(Option<Window>, String),
+) -> Result<(), QuackError> {
+    select_property_changes(x, x.root, true);
+    let mut active = x.active_window();
+    let mut title = active.map(|w| x.window_title(w)).unwrap_or_default();
+    if let Some(window) = active {
+        select_property_changes(x, window, true);
+    }
+    x.conn.flush()?;
+    on_change(active, title.clone());
+
+    loop {
+        let event = x.conn.wait_for_event()?;
+        let Event::PropertyNotify(event) = event else {
+            // Errors from windows that vanished under us arrive here as well.
+            continue;
+        };
+
+        if event.window == x.root && event.atom == x.atoms._NET_ACTIVE_WINDOW {
+            let now = x.active_window();
+            if now == active {
+                continue;
+            }
+            if let Some(old) = active {
+                select_property_changes(x, old, false);
+            }
+            if let Some(new) = now {
+                select_property_changes(x, new, true);
+            }
+            x.conn.flush()?;
+            active = now;
+            title = now.map(|w| x.window_title(w)).unwrap_or_default();
+            on_change(active, title.clone());
+        } else if Some(event.window) == active
+            && (event.atom == x.atoms._NET_WM_NAME || event.atom == u32::from(AtomEnum::WM_NAME))
+        {
+            let now = x.window_title(event.window);
+            if now != title {
+                title = now;
+                on_change(active, title.clone());
+            }
+        }
+    }
+}
+
+/// Encodes an RGBA image as a Base64 PNG.
+fn rgba_to_base64_png(img: &RgbaImage) -> Option<String> {
+    let mut png_bytes = Vec::new();
+    PngEncoder::new(&mut png_bytes)
+        .write_image(img, img.width(), img.height(), ColorType::Rgba8)
+        .ok()?;
+    Some(general_purpose::STANDARD.encode(&png_bytes))
+}
+
+/// [`Platform`] backend for X11 sessions with an EWMH-compliant window manager.
+pub struct X11Platform {
+    x: Option<X11Connection>,
+}
+
+impl X11Platform {
+    pub fn new() -> Self {
+        let x = X11Connection::connect()
+            .map_err(|e| eprintln!("X11 backend unavailable: {}", e))
+            .ok();
+        X11Platform { x }
+    }
+
+    fn x(&self) -> Result<&X11Connection, QuackError> {
+        self.x
+            .as_ref()
+            .ok_or_else(|| QuackError::os("No X11 display available"))
+    }
+}
+
+fn xid(window: WindowId) -> Window {
+    window.as_raw() as Window
+}
+
+impl Platform for X11Platform {
+    fn foreground_window(&self) -> Option<WindowId> {
+        let window = self.x().ok()?.active_window()?;
+        WindowId::from_raw(window as isize)
+    }
+
+    fn list_windows(&self) -> Vec<NativeWindow> {
+        let Ok(x) = self.x() else {
+            return Vec::new();
+        };
+        x.stacking_order()
+            .into_iter()
+            .filter_map(|w| {
+                Some(NativeWindow {
+                    id: WindowId::from_raw(w as isize)?,
+                    bounds: x.window_bounds(w)?,
+                    cloaked: x.on_other_desktop(w),
+                    tool_window: x.is_tool_window(w),
+                })
+            })
+            .collect()
+    }
+
+    fn window_title(&self, window: WindowId) -> String {
+        self.x()
+            .map(|x| x.window_title(xid(window)))
+            .unwrap_or_default()
+    }
+
+    fn window_pid(&self, window: WindowId) -> Option<u32> {
+        self.x().ok()?.window_pid(xid(window))
+    }
+
+    fn window_class(&self, window: WindowId) -> Option<String> {
+        self.x().ok()?.window_class(xid(window))
+    }
+
+    fn exe_path(&self, window: WindowId) -> Option<PathBuf> {
+        let pid = self.window_pid(window)?;
+        std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+    }
+
+    fn window_icon_base64(&self, window: WindowId) -> Option<String> {
+        let x = self.x().ok()?;
+        if let Some(icon) = x
+            .window_icon(xid(window))
+            .and_then(|i| rgba_to_base64_png(&i))
+        {
+            return Some(icon);
+        }
+        let (instance, class) = x.wm_class(xid(window))?;
+        // Icon themes only ship whole-number scales (`@2x`); 150% rounds up.
+        let scale = capture::scale_factor(x).round().max(1.0) as u32;
+        let png = freedesktop::icon_png_for_class(&instance, &class, ICON_SIZE, scale)?;
+        Some(general_purpose::STANDARD.encode(png))
+    }
+
+    fn icon_key(&self, window: WindowId) -> Option<String> {
+        // Interpreted apps (python, electron, java, ...) share an executable but
+        // not a WM_CLASS, so prefer the class.
+        self.window_class(window)
+            .map(|class| format!("wmclass:{}", class))
+            .or_else(|| {
+                self.exe_path(window)
+                    .map(|p| p.to_string_lossy().into_owned())
+            })
+    }
+
+    fn focus_window(&self, window: WindowId) -> Result<(), QuackError> {
+        self.x()?.activate_window(xid(window))
+    }
+
+    fn watch_foreground(
+        &self,
+        on_change: &mut dyn FnMut(Option<WindowId>, String),
+    ) -> Result<(), QuackError> {
+        // Blocking on events needs a dedicated connection so it doesn't swallow
+        // replies meant for the shared one.
+        let x = X11Connection::connect()?;
+        watch_active_window(&x, &mut |window, title| {
+            on_change(window.and_then(|w| WindowId::from_raw(w as isize)), title)
+        })
+    }
+
+    fn send_input(&self, events: &[InputEvent]) -> Result<(), QuackError> {
+        input::send_input_events(self.x()?, events)
+    }
+
+    /// Windows of processes running as root count as elevated.
+    fn is_elevated(&self, window: WindowId) -> bool {
+        self.window_pid(window)
+            .and_then(|pid| std::fs::metadata(format!("/proc/{}", pid)).ok())
+            .is_some_and(|m| m.uid() == 0)
+    }
+
+    /// X11 has no separate secure desktop. A running screensaver, where
+    /// lockers draw, or a focused polkit or pinentry prompt plays the same
+    /// role. Neither check grabs the keyboard, so lockers that leave the
+    /// screensaver state alone and don't take focus go unnoticed.
+    fn on_secure_desktop(&self) -> bool {
+        self.x()
+            .is_ok_and(|x| x.screensaver_active() || x.auth_prompt_active())
+    }
+
+    fn inject_text(&self, window: WindowId, text: &str) -> Result<(), QuackError> {
+        let x = self.x()?;
+        x.activate_window(xid(window))?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        input::send_input_events(x, &plan_text(text))
+    }
+
+    fn paste_text(&self, window: WindowId, content: &PasteContent) -> Result<(), QuackError> {
+        // Owning the clipboard means serving requests, so this gets its own connection too.
+        clipboard::paste(xid(window), content)
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError> {
+        capture::monitors(self.x()?)
+    }
+
+    fn capture_screen(&self, target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+        let x = self.x()?;
+        let monitor = capture::resolve_target(x, target)?;
+        let bounds = match &monitor {
+            Some(monitor) => monitor.bounds,
+            None => x
+                .window_bounds(x.root)
+                .ok_or_else(|| QuackError::capture("root geometry"))?,
+        };
+        Ok(ScreenCapture {
+            image: capture::capture_region(x, bounds)?,
+            bounds,
+            monitor,
+        })
+    }
+
+    fn capture_region(&self, bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+        capture::capture_region(self.x()?, bounds)
+    }
+
+    fn capture_window(&self, window: WindowId) -> Result<RgbaImage, QuackError> {
+        capture::capture_window(self.x()?, xid(window))
+    }
+}
+
+impl ClipboardWatcher for X11Platform {
+    fn watch_clipboard(
+        &self,
+        on_change: &mut dyn FnMut(ClipboardChange) -> bool,
+    ) -> Result<(), QuackError> {
+        // Like `watch_foreground`, this blocks on events and needs its own connection.
+        let x = X11Connection::connect()?;
+        clipboard::watch(&x, on_change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+    use x11rb::{
+        protocol::xproto::{CreateWindowAux, PropMode, WindowClass},
+        wrapper::ConnectionExt as _,
+        COPY_FROM_PARENT,
+    };
+
+    pub(super) const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Connects to `$QUACK_TEST_DISPLAY`, or `$DISPLAY` when that's unset.
+    /// The tests that need this are ignored by default; run them against Xvfb
+    /// with `Xvfb :99 & QUACK_TEST_DISPLAY=:99 cargo test -- --ignored x11`.
+    pub(super) fn test_display() -> X11Connection {
+        let display = std::env::var("QUACK_TEST_DISPLAY").ok();
+        X11Connection::connect_to(display.as_deref()).expect("connect to the test X server")
+    }
+
+    /// Creates and maps a top-level window filled with `background` and waits
+    /// for the server to have drawn it.
+    pub(super) fn create_window(
+        x: &X11Connection,
+        bounds: WindowBounds,
+        background: u32,
+        events: EventMask,
+    ) -> Window {
+        let window = x.conn.generate_id().expect("window id");
+        x.conn
+            .create_window(
+                COPY_FROM_PARENT as u8,
+                window,
+                x.root,
+                bounds.x as i16,
+                bounds.y as i16,
+                bounds.width as u16,
+                bounds.height as u16,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new()
+                    .background_pixel(background)
+                    .event_mask(events),
+            )
+            .expect("create window");
+        x.conn.map_window(window).expect("map window");
+        x.conn.sync().expect("sync");
+        window
+    }
+
+    fn set_title(x: &X11Connection, window: Window, title: &str) {
+        x.conn
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                x.atoms._NET_WM_NAME,
+                x.atoms.UTF8_STRING,
+                title.as_bytes(),
+            )
+            .expect("set title");
+        x.conn.flush().expect("flush");
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn watcher_reports_focus_and_title_changes() {
+        let x = test_display();
+        let bounds = WindowBounds {
+            x: 0,
+            y: 0,
+            width: 50,
+            height: 50,
+        };
+        let window = create_window(&x, bounds, 0, EventMask::NO_EVENT);
+        set_title(&x, window, "First title");
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let watcher = test_display();
+            let _ = watch_active_window(&watcher, &mut |active, title| {
+                let _ = tx.send((active, title));
+            });
+        });
+        // The initial report comes after the watcher subscribed to changes.
+        rx.recv_timeout(TIMEOUT).expect("initial report");
+
+        // There's no window manager to do this on Xvfb, so play its part.
+        x.conn
+            .change_property32(
+                PropMode::REPLACE,
+                x.root,
+                x.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                &[window],
+            )
+            .expect("set active window");
+        x.conn.flush().expect("flush");
+        assert_eq!(
+            rx.recv_timeout(TIMEOUT).expect("focus change"),
+            (Some(window), "First title".to_string())
+        );
+
+        set_title(&x, window, "Second title");
+        assert_eq!(
+            rx.recv_timeout(TIMEOUT).expect("title change"),
+            (Some(window), "Second title".to_string())
+        );
+    }
+
+    #[test]
+    fn recognises_auth_prompt_classes() {
+        let cases = [
+            (
+                "polkit-gnome-authentication-agent-1",
+                "Polkit-gnome-authentication-agent-1",
+                true,
+            ),
+            ("pinentry", "Pinentry-gtk-2", true),
+            ("gcr-prompter", "Gcr-prompter", true),
+            ("ssh-askpass", "", true),
+            ("i3lock", "i3lock", true),
+            ("navigator", "firefox", false),
+            ("gnome-terminal-server", "Gnome-terminal", false),
+        ];
+        for (instance, class, expected) in cases {
+            assert_eq!(
+                is_auth_prompt_class(instance, class),
+                expected,
+                "{} / {}",
+                instance,
+                class
+            );
+        }
+    }
+}
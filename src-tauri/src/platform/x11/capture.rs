@@ -0,0 +1,418 @@
+//! Screen and window capture for X11.
+//!
+//! Screens and regions are read from the root window with `GetImage`.
+//! Windows are read from their Composite pixmap when a compositing manager is
+//! running, which works even when they are covered. Otherwise their area is
+//! cropped out of the root window, so anything on top of them shows up too.
+//! Monitors come from RandR 1.5, and the scale factor from `Xft.dpi`.
+
+use super::X11Connection;
+use crate::error::QuackError;
+use crate::platform::{MonitorInfo, ScreenTarget, WindowBounds};
+use image::{Rgba, RgbaImage};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        composite::{self, ConnectionExt as _},
+        randr::ConnectionExt as _,
+        xproto::{ConnectionExt as _, Drawable, ImageFormat, ImageOrder, Visualid, Window},
+    },
+    resource_manager,
+};
+
+/// DPI at 100% display scaling.
+const BASE_DPI: f64 = 96.0;
+
+/// How the pixels of one visual are laid out in a `ZPixmap` image.
+struct PixelFormat {
+    bits_per_pixel: u8,
+    scanline_pad: u8,
+    masks: [u32; 3],
+    msb_first: bool,
+}
+
+impl PixelFormat {
+    fn of(x: &X11Connection, depth: u8, visual: Visualid) -> Result<Self, QuackError> {
+        let setup = x.conn.setup();
+        let format = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .ok_or_else(|| QuackError::capture("pixmap format"))?;
+        let visual = setup
+            .roots
+            .iter()
+            .flat_map(|s| &s.allowed_depths)
+            .flat_map(|d| &d.visuals)
+            .find(|v| v.visual_id == visual)
+            .ok_or_else(|| QuackError::capture("visual"))?;
+        if !matches!(format.bits_per_pixel, 16 | 24 | 32) {
+            return Err(QuackError::unsupported("Capture at this color depth"));
+        }
+        Ok(PixelFormat {
+            bits_per_pixel: format.bits_per_pixel,
+            scanline_pad: format.scanline_pad,
+            masks: [visual.red_mask, visual.green_mask, visual.blue_mask],
+            msb_first: setup.image_byte_order == ImageOrder::MSB_FIRST,
+        })
+    }
+
+    /// Converts `GetImage` data to opaque RGBA.
+    fn to_rgba(&self, data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+        let bytes_per_pixel = self.bits_per_pixel as usize / 8;
+        let pad = self.scanline_pad as usize;
+        let stride = (width as usize * self.bits_per_pixel as usize).div_ceil(pad) * pad / 8;
+        if data.len() < stride * height as usize {
+            return None;
+        }
+
+        let channel = |pixel: u32, mask: u32| {
+            let max = mask >> mask.trailing_zeros();
+            (((pixel & mask) >> mask.trailing_zeros()) * 255 / max.max(1)) as u8
+        };
+        let mut image = RgbaImage::new(width, height);
+        for (y, row) in data.chunks_exact(stride).take(height as usize).enumerate() {
+            for (x, bytes) in row
+                .chunks_exact(bytes_per_pixel)
+                .take(width as usize)
+                .enumerate()
+            {
+                let pixel = bytes.iter().enumerate().fold(0u32, |acc, (i, &b)| {
+                    let shift = if self.msb_first {
+                        8 * (bytes_per_pixel - 1 - i)
+                    } else {
+                        8 * i
+                    };
+                    acc | (b as u32) << shift
+                });
+                let [r, g, b] = self.masks.map(|mask| channel(pixel, mask));
+                image.put_pixel(x as u32, y as u32, Rgba([r, g, b, 255]));
+            }
+        }
+        Some(image)
+    }
+}
+
+/// Reads a rectangle of `drawable`, which must lie entirely inside it.
+fn get_image(
+    x: &X11Connection,
+    drawable: Drawable,
+    visual: Visualid,
+    bounds: WindowBounds,
+) -> Result<RgbaImage, QuackError> {
+    let reply = x
+        .conn
+        .get_image(
+            ImageFormat::Z_PIXMAP,
+            drawable,
+            bounds.x as i16,
+            bounds.y as i16,
+            bounds.width as u16,
+            bounds.height as u16,
+            !0,
+        )?
+        .reply()?;
+    // Pixmaps report no visual; the caller passes the window's instead.
+    let visual = if reply.visual != 0 {
+        reply.visual
+    } else {
+        visual
+    };
+    PixelFormat::of(x, reply.depth, visual)?
+        .to_rgba(&reply.data, bounds.width, bounds.height)
+        .ok_or_else(|| QuackError::capture("GetImage"))
+}
+
+fn root_bounds(x: &X11Connection) -> Result<WindowBounds, QuackError> {
+    x.window_bounds(x.root)
+        .ok_or_else(|| QuackError::capture("root geometry"))
+}
+
+fn intersect(a: WindowBounds, b: WindowBounds) -> Option<WindowBounds> {
+    let left = a.x.max(b.x);
+    let top = a.y.max(b.y);
+    let right = (a.x + a.width as i32).min(b.x + b.width as i32);
+    let bottom = (a.y + a.height as i32).min(b.y + b.height as i32);
+    (right > left && bottom > top).then(|| WindowBounds {
+        x: left,
+        y: top,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+/// Captures a rectangle of the root window. Parts outside the screen come out
+/// black, as they do with `BitBlt` on Windows.
+pub fn capture_region(x: &X11Connection, bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+    if bounds.width == 0 || bounds.height == 0 {
+        return Err(QuackError::capture("capture size"));
+    }
+    let root = root_bounds(x)?;
+    let visible = intersect(bounds, root).ok_or_else(|| QuackError::capture("region"))?;
+    let screen = &x.conn.setup().roots[x.screen_num];
+    let captured = get_image(x, x.root, screen.root_visual, visible)?;
+    if visible == bounds {
+        return Ok(captured);
+    }
+    let mut image = RgbaImage::from_pixel(bounds.width, bounds.height, Rgba([0, 0, 0, 255]));
+    image::imageops::replace(
+        &mut image,
+        &captured,
+        (visible.x - bounds.x) as i64,
+        (visible.y - bounds.y) as i64,
+    );
+    Ok(image)
+}
+
+/// `Xft.dpi` relative to 96 DPI, the scale toolkits apply on every monitor.
+pub fn scale_factor(x: &X11Connection) -> f64 {
+    resource_manager::new_from_default(&x.conn)
+        .ok()
+        .and_then(|db| db.get_value::<f64>("Xft.dpi", "").ok().flatten())
+        .map_or(1.0, |dpi| dpi / BASE_DPI)
+}
+
+/// Monitors from RandR, primary first and the rest left to right. Without
+/// RandR 1.5 the whole screen counts as one monitor.
+pub fn monitors(x: &X11Connection) -> Result<Vec<MonitorInfo>, QuackError> {
+    let scale_factor = scale_factor(x);
+    let randr = x
+        .conn
+        .randr_query_version(1, 5)
+        .ok()
+        .and_then(|c| c.reply().ok())
+        .filter(|v| (v.major_version, v.minor_version) >= (1, 5))
+        .and_then(|_| x.conn.randr_get_monitors(x.root, true).ok()?.reply().ok());
+
+    let mut monitors: Vec<MonitorInfo> = match randr {
+        Some(reply) if !reply.monitors.is_empty() => reply
+            .monitors
+            .iter()
+            .map(|m| MonitorInfo {
+                index: 0,
+                name: x
+                    .conn
+                    .get_atom_name(m.name)
+                    .ok()
+                    .and_then(|c| c.reply().ok())
+                    .map(|r| String::from_utf8_lossy(&r.name).into_owned())
+                    .unwrap_or_default(),
+                bounds: WindowBounds {
+                    x: m.x as i32,
+                    y: m.y as i32,
+                    width: m.width as u32,
+                    height: m.height as u32,
+                },
+                scale_factor,
+                primary: m.primary,
+            })
+            .collect(),
+        _ => vec![MonitorInfo {
+            index: 0,
+            name: "screen".to_string(),
+            bounds: root_bounds(x)?,
+            scale_factor,
+            primary: true,
+        }],
+    };
+    monitors.sort_by_key(|m| (!m.primary, m.bounds.x, m.bounds.y));
+    for (index, monitor) in monitors.iter_mut().enumerate() {
+        monitor.index = index;
+    }
+    Ok(monitors)
+}
+
+/// The monitor a [`ScreenTarget`] refers to, or `None` for the whole screen.
+pub fn resolve_target(
+    x: &X11Connection,
+    target: ScreenTarget,
+) -> Result<Option<MonitorInfo>, QuackError> {
+    let monitors = monitors(x)?;
+    let covering = |area: WindowBounds| {
+        let overlap = |m: &MonitorInfo| {
+            intersect(m.bounds, area).map_or(0, |b| b.width as u64 * b.height as u64)
+        };
+        monitors
+            .iter()
+            .filter(|m| overlap(m) > 0)
+            .max_by_key(|m| overlap(m))
+            .cloned()
+    };
+    let monitor = match target {
+        ScreenTarget::VirtualDesktop => return Ok(None),
+        ScreenTarget::Primary => monitors.first().cloned(),
+        ScreenTarget::Monitor(index) => {
+            return monitors.get(index).cloned().map(Some).ok_or_else(|| {
+                QuackError::invalid_argument(format!("No monitor at index {}", index))
+            })
+        }
+        ScreenTarget::Cursor => {
+            let pointer = x.conn.query_pointer(x.root)?.reply()?;
+            covering(WindowBounds {
+                x: pointer.root_x as i32,
+                y: pointer.root_y as i32,
+                width: 1,
+                height: 1,
+            })
+        }
+        ScreenTarget::Window(window) => {
+            let bounds = x
+                .window_bounds(window.as_raw() as Window)
+                .ok_or_else(|| QuackError::invalid_argument("The window is not on any monitor"))?;
+            let monitor = covering(bounds);
+            if monitor.is_none() {
+                return Err(QuackError::invalid_argument(
+                    "The window is not on any monitor",
+                ));
+            }
+            monitor
+        }
+    };
+    monitor
+        .map(Some)
+        .ok_or_else(|| QuackError::capture("monitor lookup"))
+}
+
+/// Whether a compositing manager owns `_NET_WM_CM_S<screen>`.
+fn compositing(x: &X11Connection) -> bool {
+    if x.conn
+        .extension_information(composite::X11_EXTENSION_NAME)
+        .ok()
+        .flatten()
+        .is_none()
+    {
+        return false;
+    }
+    let name = format!("_NET_WM_CM_S{}", x.screen_num);
+    x.conn
+        .intern_atom(true, name.as_bytes())
+        .ok()
+        .and_then(|c| c.reply().ok())
+        .filter(|r| r.atom != 0)
+        .and_then(|r| x.conn.get_selection_owner(r.atom).ok()?.reply().ok())
+        .is_some_and(|r| r.owner != 0)
+}
+
+/// The child of the root window that contains `window`, usually the window
+/// manager's frame around it.
+fn top_level(x: &X11Connection, mut window: Window) -> Result<Window, QuackError> {
+    loop {
+        let tree = x.conn.query_tree(window)?.reply()?;
+        if tree.parent == x.root || tree.parent == 0 {
+            return Ok(window);
+        }
+        window = tree.parent;
+    }
+}
+
+/// Reads the window from the off-screen pixmap the compositor renders from.
+/// Only top-level windows are redirected, so this names the frame's pixmap
+/// and crops the client area out of it.
+fn capture_composited(x: &X11Connection, window: Window) -> Result<RgbaImage, QuackError> {
+    x.conn.composite_query_version(0, 4)?.reply()?;
+    let frame = top_level(x, window)?;
+    let frame_geometry = x.conn.get_geometry(frame)?.reply()?;
+    let geometry = x.conn.get_geometry(window)?.reply()?;
+    let offset = x.conn.translate_coordinates(window, frame, 0, 0)?.reply()?;
+    let visual = x.conn.get_window_attributes(frame)?.reply()?.visual;
+
+    let pixmap = x.conn.generate_id().map_err(QuackError::os)?;
+    x.conn
+        .composite_name_window_pixmap(frame, pixmap)?
+        .check()?;
+    // The pixmap includes the frame's border; window coordinates don't.
+    let border = frame_geometry.border_width as i32;
+    let image = get_image(
+        x,
+        pixmap,
+        visual,
+        WindowBounds {
+            x: offset.dst_x as i32 + border,
+            y: offset.dst_y as i32 + border,
+            width: geometry.width as u32,
+            height: geometry.height as u32,
+        },
+    );
+    let _ = x.conn.free_pixmap(pixmap);
+    image
+}
+
+/// Captures a client window, through Composite when possible and by cropping
+/// the screen otherwise.
+pub fn capture_window(x: &X11Connection, window: Window) -> Result<RgbaImage, QuackError> {
+    let bounds = x
+        .window_bounds(window)
+        .ok_or_else(|| QuackError::capture("window geometry"))?;
+    if bounds.width == 0 || bounds.height == 0 {
+        return Err(QuackError::capture("window size"));
+    }
+    if compositing(x) {
+        if let Ok(image) = capture_composited(x, window) {
+            return Ok(image);
+        }
+    }
+    capture_region(x, bounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{create_window, test_display};
+    use super::*;
+    use x11rb::protocol::xproto::EventMask;
+
+    /// Orange in a 24-bit TrueColor visual, the Xvfb default.
+    const ORANGE_PIXEL: u32 = 0xff8000;
+    const ORANGE: Rgba<u8> = Rgba([0xff, 0x80, 0x00, 0xff]);
+
+    // Tests run in parallel, so each one's windows get a spot of the screen
+    // no other test draws on.
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn captures_a_window_of_known_colour() {
+        let x = test_display();
+        assert_eq!(x.conn.setup().roots[x.screen_num].root_depth, 24);
+        let bounds = WindowBounds {
+            x: 100,
+            y: 20,
+            width: 40,
+            height: 30,
+        };
+        let window = create_window(&x, bounds, ORANGE_PIXEL, EventMask::NO_EVENT);
+
+        let image = capture_window(&x, window).expect("capture window");
+        assert_eq!(image.dimensions(), (40, 30));
+        assert!(image.pixels().all(|&p| p == ORANGE));
+
+        let image = capture_region(&x, bounds).expect("capture region");
+        assert_eq!(image.dimensions(), (40, 30));
+        assert!(image.pixels().all(|&p| p == ORANGE));
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn pads_regions_off_the_screen_with_black() {
+        let x = test_display();
+        let bounds = WindowBounds {
+            x: 0,
+            y: 200,
+            width: 20,
+            height: 20,
+        };
+        create_window(&x, bounds, ORANGE_PIXEL, EventMask::NO_EVENT);
+
+        let image = capture_region(
+            &x,
+            WindowBounds {
+                x: -10,
+                y: 200,
+                width: 20,
+                height: 20,
+            },
+        )
+        .expect("capture region");
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(19, 19), ORANGE);
+    }
+}
@@ -0,0 +1,738 @@
+//! Watching the `CLIPBOARD` selection. XFixes reports every change of its
+//! owner, and the new contents are then requested from the owner with
+//! `ConvertSelection` like any other client would paste them.
+//!
+//! Paste injection takes the other side: we own `CLIPBOARD` while the target
+//! fetches the text, then keep serving the previous contents from a thread,
+//! since an X11 selection lives only as long as its owner.
+
+use super::{input, X11Connection};
+use crate::error::QuackError;
+use crate::injection::{
+    plan::{plan_chord, NamedKey},
+    PasteContent,
+};
+use crate::platform::{ClipContent, ClipboardChange};
+use image::ImageFormat;
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    os::unix::ffi::OsStringExt,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+            SelectionNotifyEvent, SelectionRequestEvent, Timestamp, Window, WindowClass,
+            SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+/// How long the clipboard owner gets to answer a conversion request, and
+/// then to send each further chunk of an incremental transfer.
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(500);
+/// Incremental transfers are abandoned beyond this size.
+const MAX_TRANSFER_BYTES: usize = 64 * 1024 * 1024;
+/// How long the target gets to fetch a paste before we restore the clipboard.
+const PASTE_TIMEOUT: Duration = Duration::from_millis(750);
+/// How long the target stays quiet after fetching the text before we take
+/// the paste as done.
+const PASTE_IDLE: Duration = Duration::from_millis(150);
+/// How many of our owner windows [`watch`] remembers.
+const OWN_WINDOWS_KEPT: usize = 16;
+
+/// Windows we recently owned `CLIPBOARD` with. Our paste content and the
+/// restored clipboard aren't new copies, so [`watch`] skips them. Kept past
+/// the window's lifetime because the watcher may see the change late.
+static OWN_WINDOWS: Mutex<VecDeque<Window>> = Mutex::new(VecDeque::new());
+
+fn remember_own_window(window: Window) {
+    let mut windows = OWN_WINDOWS.lock().unwrap_or_else(|e| e.into_inner());
+    if windows.len() == OWN_WINDOWS_KEPT {
+        windows.pop_front();
+    }
+    windows.push_back(window);
+}
+
+fn is_own_window(window: Window) -> bool {
+    OWN_WINDOWS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(&window)
+}
+
+x11rb::atom_manager! {
+    pub ClipboardAtoms: ClipboardAtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        MULTIPLE,
+        TIMESTAMP,
+        SAVE_TARGETS,
+        INCR,
+        TEXT,
+        UTF8_STRING,
+        // Property on our window the owner writes the converted data to.
+        QUACK_CLIPBOARD,
+        // KDE's hint, also offered by KeePassXC, that the content is a secret.
+        PASSWORD_MANAGER_HINT: b"x-kde-passwordManagerHint",
+        URI_LIST: b"text/uri-list",
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_HTML: b"text/html",
+        TEXT_RTF: b"text/rtf",
+        APPLICATION_RTF: b"application/rtf",
+        IMAGE_PNG: b"image/png",
+        // Firefox's record of the page a selection was copied from.
+        MOZ_URL_PRIV: b"text/x-moz-url-priv",
+    }
+}
+
+/// Firefox offers some targets as UTF-16, with or without a BOM.
+fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = bytes.starts_with(&[0xFF, 0xFE])
+        || (bytes.len() >= 2 && bytes.len().is_multiple_of(2) && bytes[1] == 0);
+    if !utf16 {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_start_matches('\u{feff}')
+        .to_string()
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// The local paths in a `text/uri-list`; other URIs are skipped.
+fn parse_uri_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|uri| uri.strip_prefix("file://"))
+        // Drops the host part of `file://host/path`, usually empty.
+        .filter_map(|rest| rest.find('/').map(|slash| &rest[slash..]))
+        .map(|path| OsString::from_vec(percent_decode(path)).into())
+        .collect()
+}
+
+/// Data for one target, as its owner typed it. Used both for what we offer
+/// when pasting and for the previous clipboard contents we put back.
+struct Offer {
+    target: Atom,
+    ty: Atom,
+    format: u8,
+    data: Vec<u8>,
+}
+
+/// A hidden window that receives the selection events.
+struct Requestor<'a> {
+    x: &'a X11Connection,
+    window: Window,
+    atoms: ClipboardAtoms,
+    /// Events that arrived while waiting for a conversion.
+    pending: VecDeque<Event>,
+}
+
+impl<'a> Requestor<'a> {
+    fn new(x: &'a X11Connection) -> Result<Self, QuackError> {
+        let atoms = ClipboardAtoms::new(&x.conn)?.reply()?;
+        let window = x.conn.generate_id().map_err(QuackError::os)?;
+        x.conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            x.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            // Incremental transfers are driven by property changes.
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        Ok(Requestor {
+            x,
+            window,
+            atoms,
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn next_event(&mut self) -> Result<Event, QuackError> {
+        match self.pending.pop_front() {
+            Some(event) => Ok(event),
+            None => Ok(self.x.conn.wait_for_event()?),
+        }
+    }
+
+    /// Waits up to [`TRANSFER_TIMEOUT`] for an event `wanted` accepts,
+    /// keeping the others for [`Self::next_event`].
+    fn wait_for(&mut self, wanted: impl Fn(&Event) -> bool) -> Option<Event> {
+        let deadline = Instant::now() + TRANSFER_TIMEOUT;
+        loop {
+            match self.x.conn.poll_for_event().ok()? {
+                Some(event) if wanted(&event) => return Some(event),
+                Some(event) => self.pending.push_back(event),
+                None if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                None => return None,
+            }
+        }
+    }
+
+    /// Reads and deletes our transfer property, returning its type, format
+    /// and data.
+    fn take_property(&self) -> Option<(Atom, u8, Vec<u8>)> {
+        let reply = self
+            .x
+            .conn
+            .get_property(
+                true,
+                self.window,
+                self.atoms.QUACK_CLIPBOARD,
+                AtomEnum::ANY,
+                0,
+                u32::MAX,
+            )
+            .ok()?
+            .reply()
+            .ok()?;
+        self.x.conn.flush().ok()?;
+        Some((reply.type_, reply.format, reply.value))
+    }
+
+    /// Follows the INCR protocol: the owner writes the data in chunks, each
+    /// after we delete the previous one, and ends with an empty chunk. Each
+    /// chunk carries the type of the data.
+    fn receive_incremental(&mut self) -> Option<(Atom, u8, Vec<u8>)> {
+        let (window, property) = (self.window, self.atoms.QUACK_CLIPBOARD);
+        let mut data = Vec::new();
+        loop {
+            self.wait_for(|event| {
+                matches!(event, Event::PropertyNotify(e)
+                    if e.window == window && e.atom == property && e.state == Property::NEW_VALUE)
+            })?;
+            let (ty, format, chunk) = self.take_property()?;
+            if chunk.is_empty() {
+                return Some((ty, format, data));
+            }
+            data.extend_from_slice(&chunk);
+            if data.len() > MAX_TRANSFER_BYTES {
+                return None;
+            }
+        }
+    }
+
+    /// Asks the clipboard owner for `target` and waits for its answer. Gives
+    /// up after [`TRANSFER_TIMEOUT`] so a stuck owner can't stall the watcher.
+    fn convert(&mut self, target: Atom, time: Timestamp) -> Option<Vec<u8>> {
+        self.convert_typed(target, time).map(|offer| offer.data)
+    }
+
+    /// Like [`Self::convert`], but keeps the type and format of the data.
+    fn convert_typed(&mut self, target: Atom, time: Timestamp) -> Option<Offer> {
+        let window = self.window;
+        self.x
+            .conn
+            .convert_selection(
+                window,
+                self.atoms.CLIPBOARD,
+                target,
+                self.atoms.QUACK_CLIPBOARD,
+                time,
+            )
+            .ok()?;
+        self.x.conn.flush().ok()?;
+        let Event::SelectionNotify(notify) = self.wait_for(
+            |event| matches!(event, Event::SelectionNotify(e) if e.requestor == window),
+        )?
+        else {
+            return None;
+        };
+        if notify.property == NONE {
+            return None;
+        }
+        let (mut ty, mut format, mut data) = self.take_property()?;
+        if ty == self.atoms.INCR {
+            (ty, format, data) = self.receive_incremental()?;
+        }
+        Some(Offer {
+            target,
+            ty,
+            format,
+            data,
+        })
+    }
+
+    /// The targets the owner offers, as listed in its `TARGETS`.
+    fn targets(&mut self, time: Timestamp) -> Option<Vec<Atom>> {
+        self.convert(self.atoms.TARGETS, time).map(|data| {
+            data.chunks_exact(4)
+                .map(|atom| u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+                .collect()
+        })
+    }
+
+    /// Fetches `target` if the owner offers it.
+    fn fetch(&mut self, targets: &[Atom], target: Atom, time: Timestamp) -> Option<Vec<u8>> {
+        targets
+            .contains(&target)
+            .then(|| self.convert(target, time))
+            .flatten()
+    }
+
+    /// The page an HTML selection was copied from.
+    fn source_url(&mut self, targets: &[Atom], time: Timestamp) -> Option<String> {
+        let data = self.fetch(targets, self.atoms.MOZ_URL_PRIV, time)?;
+        decode_text(&data).lines().next().map(str::to_string)
+    }
+
+    /// Reads the clipboard in the richest format offered, in the same order
+    /// of preference as on Windows: files, then text as HTML or RTF when
+    /// offered, then an image when there's no text.
+    fn read_content(&mut self, targets: &[Atom], time: Timestamp) -> Option<ClipContent> {
+        let atoms = self.atoms;
+        let paths = self
+            .fetch(targets, atoms.URI_LIST, time)
+            .map(|data| parse_uri_list(&String::from_utf8_lossy(&data)))
+            .unwrap_or_default();
+        if !paths.is_empty() {
+            return Some(ClipContent::Files(paths));
+        }
+        // Some owners answer conversions without listing their targets.
+        let text = if targets.is_empty() {
+            self.convert(atoms.UTF8_STRING, time)
+        } else {
+            self.fetch(targets, atoms.UTF8_STRING, time)
+        }
+        .map(|data| String::from_utf8_lossy(&data).into_owned());
+        let html = self
+            .fetch(targets, atoms.TEXT_HTML, time)
+            .map(|data| decode_text(&data));
+
+        if text.is_some() {
+            if let Some(fragment) = html {
+                return Some(ClipContent::Html {
+                    fragment,
+                    source_url: self.source_url(targets, time),
+                    text,
+                });
+            }
+            let rtf = self
+                .fetch(targets, atoms.TEXT_RTF, time)
+                .or_else(|| self.fetch(targets, atoms.APPLICATION_RTF, time));
+            if let Some(rtf) = rtf {
+                let rtf = String::from_utf8_lossy(&rtf).into_owned();
+                return Some(ClipContent::Rtf { rtf, text });
+            }
+            return text.map(ClipContent::Text);
+        }
+        let image = self
+            .fetch(targets, atoms.IMAGE_PNG, time)
+            .and_then(|data| image::load_from_memory_with_format(&data, ImageFormat::Png).ok());
+        if let Some(image) = image {
+            return Some(ClipContent::Image(image.to_rgba8()));
+        }
+        html.map(|fragment| ClipContent::Html {
+            fragment,
+            source_url: self.source_url(targets, time),
+            text: None,
+        })
+    }
+
+    /// Reads the clipboard as it was at `time`.
+    fn read(&mut self, time: Timestamp) -> ClipboardChange {
+        let targets = self.targets(time).unwrap_or_default();
+        ClipboardChange {
+            content: self.read_content(&targets, time),
+            exclude_from_history: targets.contains(&self.atoms.PASSWORD_MANAGER_HINT),
+        }
+    }
+
+    /// Fetches every target of the current owner so [`Owner`] can offer
+    /// them again after a paste. Targets too large to serve in one property
+    /// are dropped.
+    fn save(&mut self) -> Result<Vec<Offer>, QuackError> {
+        let owner = self
+            .x
+            .conn
+            .get_selection_owner(self.atoms.CLIPBOARD)?
+            .reply()?
+            .owner;
+        if owner == NONE {
+            return Ok(Vec::new());
+        }
+        let targets = self
+            .targets(CURRENT_TIME)
+            .ok_or_else(|| QuackError::clipboard("The clipboard owner didn't answer"))?;
+        let atoms = self.atoms;
+        let meta = [
+            atoms.TARGETS,
+            atoms.MULTIPLE,
+            atoms.TIMESTAMP,
+            atoms.SAVE_TARGETS,
+        ];
+        let max_bytes = max_property_bytes(self.x);
+        Ok(targets
+            .into_iter()
+            .filter(|target| !meta.contains(target))
+            .filter_map(|target| self.convert_typed(target, CURRENT_TIME))
+            .filter(|offer| offer.data.len() <= max_bytes)
+            .collect())
+    }
+}
+
+impl Drop for Requestor<'_> {
+    fn drop(&mut self) {
+        let _ = self.x.conn.destroy_window(self.window);
+        let _ = self.x.conn.flush();
+    }
+}
+
+/// Blocks and reports every new owner of `CLIPBOARD` until `on_change`
+/// returns `false`.
+pub fn watch(
+    x: &X11Connection,
+    on_change: &mut dyn FnMut(ClipboardChange) -> bool,
+) -> Result<(), QuackError> {
+    x.conn.xfixes_query_version(5, 0)?.reply()?;
+    let mut requestor = Requestor::new(x)?;
+    x.conn.xfixes_select_selection_input(
+        requestor.window,
+        requestor.atoms.CLIPBOARD,
+        SelectionEventMask::SET_SELECTION_OWNER,
+    )?;
+    x.conn.flush()?;
+
+    loop {
+        let Event::XfixesSelectionNotify(event) = requestor.next_event()? else {
+            continue;
+        };
+        // No owner means the clipboard was cleared, e.g. its owner quit.
+        if event.owner == NONE || is_own_window(event.owner) {
+            continue;
+        }
+        let change = requestor.read(event.selection_timestamp);
+        if !on_change(change) {
+            return Ok(());
+        }
+    }
+}
+
+/// The largest property we write in one `ChangeProperty` request. Larger
+/// data would need the INCR protocol, which we only implement for reading.
+fn max_property_bytes(x: &X11Connection) -> usize {
+    x.conn.maximum_request_bytes().saturating_sub(24)
+}
+
+/// A hidden window that owns `CLIPBOARD` and answers conversion requests
+/// from [`Offer`]s. Holds its own connection, as serving blocks on events.
+struct Owner {
+    x: X11Connection,
+    window: Window,
+    atoms: ClipboardAtoms,
+    offers: Vec<Offer>,
+}
+
+impl Owner {
+    fn new(x: X11Connection) -> Result<Self, QuackError> {
+        let atoms = ClipboardAtoms::new(&x.conn)?.reply()?;
+        let window = x.conn.generate_id().map_err(QuackError::os)?;
+        x.conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            x.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+        Ok(Owner {
+            x,
+            window,
+            atoms,
+            offers: Vec::new(),
+        })
+    }
+
+    /// Offers the text under the usual text targets, plus HTML and RTF when
+    /// given.
+    fn offer_paste(&mut self, content: &PasteContent) {
+        let atoms = self.atoms;
+        let text = |target, ty| Offer {
+            target,
+            ty,
+            format: 8,
+            data: content.text.as_bytes().to_vec(),
+        };
+        self.offers = vec![
+            text(atoms.UTF8_STRING, atoms.UTF8_STRING),
+            text(atoms.TEXT_PLAIN_UTF8, atoms.TEXT_PLAIN_UTF8),
+            text(atoms.TEXT, atoms.UTF8_STRING),
+        ];
+        let rich = [
+            (atoms.TEXT_HTML, content.html),
+            (atoms.TEXT_RTF, content.rtf),
+            (atoms.APPLICATION_RTF, content.rtf),
+        ];
+        for (target, data) in rich {
+            if let Some(data) = data {
+                self.offers.push(Offer {
+                    target,
+                    ty: target,
+                    format: 8,
+                    data: data.as_bytes().to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Makes our window the `CLIPBOARD` owner.
+    fn acquire(&self) -> Result<(), QuackError> {
+        remember_own_window(self.window);
+        self.x
+            .conn
+            .set_selection_owner(self.window, self.atoms.CLIPBOARD, CURRENT_TIME)?;
+        let owner = self
+            .x
+            .conn
+            .get_selection_owner(self.atoms.CLIPBOARD)?
+            .reply()?
+            .owner;
+        if owner != self.window {
+            return Err(QuackError::clipboard(
+                "Failed to take ownership of the clipboard",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Converts the request's target into the requestor's property and
+    /// tells it whether that worked. `MULTIPLE` isn't supported.
+    fn answer(&self, request: &SelectionRequestEvent) {
+        // Obsolete clients leave the property out and expect the target's name.
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+        let conn = &self.x.conn;
+        let stored = if request.target == self.atoms.TARGETS {
+            let mut targets = vec![self.atoms.TARGETS];
+            targets.extend(self.offers.iter().map(|offer| offer.target));
+            conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &targets,
+            )
+            .is_ok()
+        } else {
+            let max_bytes = max_property_bytes(&self.x);
+            self.offers
+                .iter()
+                .find(|offer| offer.target == request.target && offer.data.len() <= max_bytes)
+                .is_some_and(|offer| {
+                    let units = offer.data.len() / (usize::from(offer.format.max(8)) / 8);
+                    conn.change_property(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        offer.ty,
+                        offer.format,
+                        units as u32,
+                        &offer.data,
+                    )
+                    .is_ok()
+                })
+        };
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { NONE },
+        };
+        let _ = conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify);
+        let _ = conn.flush();
+    }
+
+    /// Activates `window` and sends it Ctrl+V.
+    fn send_shortcut(&self, window: Window) -> Result<(), QuackError> {
+        self.x.activate_window(window)?;
+        std::thread::sleep(Duration::from_millis(50));
+        input::send_input_events(
+            &self.x,
+            &plan_chord(NamedKey::Control, NamedKey::Letter('V')),
+        )
+    }
+
+    /// Answers the target's requests until it has been quiet for
+    /// [`PASTE_IDLE`] after fetching data, or for at most [`PASTE_TIMEOUT`].
+    /// Returns `false` if another client took the clipboard meanwhile.
+    fn serve_paste(&self) -> bool {
+        let started = Instant::now();
+        let mut last_fetch: Option<Instant> = None;
+        while started.elapsed() < PASTE_TIMEOUT {
+            match self.x.conn.poll_for_event() {
+                Ok(Some(Event::SelectionRequest(request))) => {
+                    self.answer(&request);
+                    if request.target != self.atoms.TARGETS {
+                        last_fetch = Some(Instant::now());
+                    }
+                }
+                Ok(Some(Event::SelectionClear(_))) | Err(_) => return false,
+                Ok(Some(_)) => {}
+                Ok(None) if last_fetch.is_some_and(|at| at.elapsed() >= PASTE_IDLE) => {
+                    return true;
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        true
+    }
+
+    /// Answers requests until another client takes the clipboard.
+    fn serve_until_replaced(&self) {
+        while let Ok(event) = self.x.conn.wait_for_event() {
+            match event {
+                Event::SelectionRequest(request) => self.answer(&request),
+                Event::SelectionClear(_) => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Puts `saved` back on the clipboard, served from a thread until the
+    /// user copies something else. Clears the clipboard if it was empty.
+    fn restore(mut self, saved: Vec<Offer>) -> Result<(), QuackError> {
+        if saved.is_empty() {
+            self.x
+                .conn
+                .set_selection_owner(NONE, self.atoms.CLIPBOARD, CURRENT_TIME)?;
+            return Ok(());
+        }
+        self.offers = saved;
+        self.acquire()?;
+        std::thread::spawn(move || self.serve_until_replaced());
+        Ok(())
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let _ = self.x.conn.destroy_window(self.window);
+        let _ = self.x.conn.flush();
+    }
+}
+
+/// Pastes `content` into `window` with Ctrl+V and puts the user's clipboard
+/// back.
+///
+/// Fails with `ClipboardFailed` before Ctrl+V is sent if the clipboard can't
+/// be saved or taken over. Failing to restore it afterwards is logged rather
+/// than returned, since the text was pasted by then.
+pub fn paste(window: Window, content: &PasteContent) -> Result<(), QuackError> {
+    let x = X11Connection::connect()?;
+    let saved = Requestor::new(&x)?.save()?;
+    let mut owner = Owner::new(x)?;
+    owner.offer_paste(content);
+    owner.acquire()?;
+
+    let pasted = owner.send_shortcut(window).map(|()| owner.serve_paste());
+    // Someone copied while the target was pasting; theirs wins.
+    if let Ok(false) = pasted {
+        return Ok(());
+    }
+    if let Err(e) = owner.restore(saved) {
+        eprintln!("Failed to restore the clipboard after pasting: {}", e);
+    }
+    pasted.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::test_display;
+    use super::*;
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn serves_paste_content_to_other_clients() {
+        let mut owner = Owner::new(test_display()).expect("owner window");
+        owner.offer_paste(&PasteContent {
+            text: "quack",
+            html: Some("<b>quack</b>"),
+            rtf: None,
+        });
+        owner.acquire().expect("own the clipboard");
+        let owner_window = owner.window;
+        let server = std::thread::spawn(move || owner.serve_until_replaced());
+
+        let x = test_display();
+        let mut requestor = Requestor::new(&x).expect("requestor window");
+        let change = requestor.read(CURRENT_TIME);
+        let Some(ClipContent::Html { fragment, text, .. }) = change.content else {
+            panic!("expected HTML content");
+        };
+        assert_eq!(fragment, "<b>quack</b>");
+        assert_eq!(text.as_deref(), Some("quack"));
+        assert!(is_own_window(owner_window));
+
+        let saved = requestor.save().expect("save the clipboard");
+        let utf8 = saved
+            .iter()
+            .find(|offer| offer.target == requestor.atoms.UTF8_STRING)
+            .expect("UTF8_STRING saved");
+        assert_eq!((utf8.format, utf8.data.as_slice()), (8, &b"quack"[..]));
+        assert!(!saved
+            .iter()
+            .any(|offer| offer.target == requestor.atoms.TARGETS));
+
+        // Taking the clipboard ends the owner's serving thread.
+        x.conn
+            .set_selection_owner(requestor.window, requestor.atoms.CLIPBOARD, CURRENT_TIME)
+            .expect("take the clipboard");
+        x.conn.flush().expect("flush");
+        server.join().expect("owner thread");
+    }
+}
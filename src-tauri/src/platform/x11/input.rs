@@ -0,0 +1,287 @@
+//! Keyboard injection for X11 through the XTEST extension.
+//!
+//! XTEST sends keycodes, not characters, so each character is looked up in
+//! the current keyboard mapping. Characters the layout can't produce are typed
+//! the way xdotool does it: an unused keycode is temporarily remapped to the
+//! character's keysym, pressed, and reset to `NoSymbol` afterwards.
+
+use super::X11Connection;
+use crate::error::QuackError;
+use crate::injection::plan::{InputEvent, KeyStroke, NamedKey};
+use std::time::Duration;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{ConnectionExt as _, Keycode, Keysym, KEY_PRESS_EVENT, KEY_RELEASE_EVENT},
+        xtest::ConnectionExt as _,
+    },
+};
+
+const XK_RETURN: Keysym = 0xff0d;
+const XK_TAB: Keysym = 0xff09;
+const XK_BACKSPACE: Keysym = 0xff08;
+const XK_SHIFT_L: Keysym = 0xffe1;
+const XK_CONTROL_L: Keysym = 0xffe3;
+const NO_SYMBOL: Keysym = 0;
+
+/// Time for clients to process the `MappingNotify` of a remap before (and
+/// after) we use the remapped keycode.
+const REMAP_DELAY: Duration = Duration::from_millis(20);
+
+/// The keysym X11 uses for a character: Latin-1 maps directly, everything
+/// else uses the Unicode keysym range.
+pub fn char_to_keysym(c: char) -> Keysym {
+    let cp = c as u32;
+    if (0x20..=0x7e).contains(&cp) || (0xa0..=0xff).contains(&cp) {
+        cp
+    } else {
+        0x0100_0000 | cp
+    }
+}
+
+/// Converts planned events to keysym presses, joining surrogate pairs back
+/// into characters.
+pub fn keysym_events(events: &[InputEvent]) -> Vec<(Keysym, bool)> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut pending_high: Option<u16> = None;
+    for event in events {
+        let keysym = match event.stroke {
+            KeyStroke::Named(NamedKey::Enter) => XK_RETURN,
+            KeyStroke::Named(NamedKey::Tab) => XK_TAB,
+            KeyStroke::Named(NamedKey::Backspace) => XK_BACKSPACE,
+            KeyStroke::Named(NamedKey::Control) => XK_CONTROL_L,
+            // Shortcuts use the unshifted letter: Ctrl+v, not Ctrl+Shift+V.
+            KeyStroke::Named(NamedKey::Letter(c)) => char_to_keysym(c.to_ascii_lowercase()),
+            KeyStroke::Unicode(unit) if (0xD800..0xDC00).contains(&unit) => {
+                // High surrogate: its low half carries the whole character.
+                if event.down {
+                    pending_high = Some(unit);
+                }
+                continue;
+            }
+            KeyStroke::Unicode(unit) if (0xDC00..0xE000).contains(&unit) => {
+                let Some(high) = pending_high else { continue };
+                let Some(Ok(c)) = char::decode_utf16([high, unit]).next() else {
+                    continue;
+                };
+                char_to_keysym(c)
+            }
+            KeyStroke::Unicode(unit) => match char::from_u32(unit as u32) {
+                Some(c) => char_to_keysym(c),
+                None => continue,
+            },
+        };
+        out.push((keysym, event.down));
+    }
+    out
+}
+
+/// Snapshot of the server's keycode-to-keysym table.
+struct Keymap {
+    min_keycode: Keycode,
+    per_keycode: u8,
+    keysyms: Vec<Keysym>,
+}
+
+impl Keymap {
+    fn load(x: &X11Connection) -> Result<Self, QuackError> {
+        let setup = x.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let reply = x.conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        Ok(Keymap {
+            min_keycode: min,
+            per_keycode: reply.keysyms_per_keycode,
+            keysyms: reply.keysyms,
+        })
+    }
+
+    fn levels(&self) -> impl Iterator<Item = (Keycode, &[Keysym])> {
+        self.keysyms
+            .chunks(self.per_keycode.max(1) as usize)
+            .enumerate()
+            .map(|(i, syms)| (self.min_keycode + i as u8, syms))
+    }
+
+    /// Finds a keycode producing `keysym` unshifted or with Shift.
+    fn lookup(&self, keysym: Keysym) -> Option<(Keycode, bool)> {
+        self.levels().find_map(|(keycode, syms)| match syms {
+            [k, ..] if *k == keysym => Some((keycode, false)),
+            [_, k, ..] if *k == keysym => Some((keycode, true)),
+            _ => None,
+        })
+    }
+
+    /// An unused keycode we can borrow for characters the layout lacks.
+    fn spare_keycode(&self) -> Option<Keycode> {
+        self.levels()
+            .filter(|(_, syms)| syms.iter().all(|&s| s == NO_SYMBOL))
+            .map(|(keycode, _)| keycode)
+            .last()
+    }
+}
+
+struct Typist<'a> {
+    x: &'a X11Connection,
+    keymap: Keymap,
+    shift: Option<Keycode>,
+    spare: Option<Keycode>,
+    /// Keysym currently mapped onto the spare keycode.
+    remapped: Option<Keysym>,
+}
+
+impl<'a> Typist<'a> {
+    fn fake_key(&self, keycode: Keycode, down: bool) -> Result<(), QuackError> {
+        let kind = if down {
+            KEY_PRESS_EVENT
+        } else {
+            KEY_RELEASE_EVENT
+        };
+        self.x
+            .conn
+            .xtest_fake_input(kind, keycode, x11rb::CURRENT_TIME, self.x.root, 0, 0, 0)?;
+        Ok(())
+    }
+
+    /// Points the spare keycode at `keysym` (or back at `NoSymbol`) and waits
+    /// for the round trip so the next key event uses the new mapping.
+    fn remap_spare(&mut self, keysym: Keysym) -> Result<Keycode, QuackError> {
+        let spare = self.spare.ok_or_else(|| {
+            QuackError::os("No free keycode to type characters missing from the layout")
+        })?;
+        if self.remapped == Some(keysym) {
+            return Ok(spare);
+        }
+        let syms = vec![keysym; self.keymap.per_keycode.max(1) as usize];
+        self.x
+            .conn
+            .change_keyboard_mapping(1, spare, syms.len() as u8, &syms)?;
+        self.x.conn.get_input_focus()?.reply()?;
+        std::thread::sleep(REMAP_DELAY);
+        self.remapped = (keysym != NO_SYMBOL).then_some(keysym);
+        Ok(spare)
+    }
+
+    fn key(&mut self, keysym: Keysym, down: bool) -> Result<(), QuackError> {
+        let (keycode, shifted) = match self.keymap.lookup(keysym) {
+            Some(found) => found,
+            None => (self.remap_spare(keysym)?, false),
+        };
+        let shift = self.shift.filter(|_| shifted);
+        if down {
+            if let Some(shift) = shift {
+                self.fake_key(shift, true)?;
+            }
+            self.fake_key(keycode, true)
+        } else {
+            self.fake_key(keycode, false)?;
+            if let Some(shift) = shift {
+                self.fake_key(shift, false)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn finish(mut self) -> Result<(), QuackError> {
+        self.x.conn.flush()?;
+        if self.remapped.is_some() {
+            // Give the target time to translate the last key with our mapping.
+            std::thread::sleep(REMAP_DELAY);
+            self.remap_spare(NO_SYMBOL)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends planned key events to whichever window has keyboard focus.
+pub fn send_input_events(x: &X11Connection, events: &[InputEvent]) -> Result<(), QuackError> {
+    let keymap = Keymap::load(x)?;
+    let mut typist = Typist {
+        x,
+        shift: keymap.lookup(XK_SHIFT_L).map(|(keycode, _)| keycode),
+        spare: keymap.spare_keycode(),
+        keymap,
+        remapped: None,
+    };
+    for (keysym, down) in keysym_events(events) {
+        if let Err(e) = typist.key(keysym, down) {
+            let _ = typist.finish();
+            return Err(e);
+        }
+    }
+    typist.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{create_window, test_display, TIMEOUT};
+    use super::*;
+    use crate::injection::plan::plan_text;
+    use crate::platform::WindowBounds;
+    use std::time::Instant;
+    use x11rb::{
+        protocol::{
+            xproto::{EventMask, InputFocus},
+            Event,
+        },
+        wrapper::ConnectionExt as _,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Press(Keycode),
+        Remap,
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn focused_window_receives_typed_keys() {
+        let recorder = test_display();
+        let bounds = WindowBounds {
+            x: 0,
+            y: 0,
+            width: 50,
+            height: 50,
+        };
+        let window = create_window(&recorder, bounds, 0, EventMask::KEY_PRESS);
+        recorder
+            .conn
+            .set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)
+            .expect("focus");
+        recorder.conn.sync().expect("sync");
+
+        let x = test_display();
+        let keymap = Keymap::load(&x).expect("keymap");
+        let key = |keysym| keymap.lookup(keysym).expect("keysym in layout").0;
+        let spare = keymap.spare_keycode().expect("spare keycode");
+        // No layout has a snowman, so typing it borrows the spare keycode.
+        assert!(keymap.lookup(char_to_keysym('☃')).is_none());
+        send_input_events(&x, &plan_text("aA☃")).expect("type");
+
+        let expected = vec![
+            Seen::Press(key(char_to_keysym('a'))),
+            Seen::Press(key(XK_SHIFT_L)),
+            Seen::Press(key(char_to_keysym('a'))),
+            Seen::Remap,
+            Seen::Press(spare),
+            Seen::Remap,
+        ];
+        let mut seen = Vec::new();
+        let deadline = Instant::now() + TIMEOUT;
+        while seen.len() < expected.len() && Instant::now() < deadline {
+            match recorder.conn.poll_for_event().expect("event") {
+                Some(Event::KeyPress(event)) if event.event == window => {
+                    seen.push(Seen::Press(event.detail))
+                }
+                Some(Event::MappingNotify(event)) if event.first_keycode == spare => {
+                    seen.push(Seen::Remap)
+                }
+                Some(_) => {}
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        assert_eq!(seen, expected);
+        // The borrowed keycode is free again afterwards.
+        let after = Keymap::load(&x).expect("keymap");
+        assert_eq!(after.spare_keycode(), Some(spare));
+    }
+}
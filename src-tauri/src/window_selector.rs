@@ -0,0 +1,411 @@
+//! Flexible lookup of a target window for injection and capture.
+//!
+//! `FindWindowW` only matches exact titles, which break as soon as a browser
+//! tab or an editor's unsaved marker changes them. A [`WindowSelector`] can
+//! match by substring, regex, process name, window class or pid instead. It
+//! deserializes from `{ "kind": "title_contains", "value": "Visual Studio Code" }`.
+
+use crate::error::QuackError;
+use crate::platform::{Platform, WindowId};
+use regex::RegexBuilder;
+use std::fmt;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum WindowSelector {
+    /// Exact window title. If several windows share it, the topmost one is
+    /// used, as `FindWindowW` does.
+    Title(String),
+    /// Case-insensitive substring of the title.
+    TitleContains(String),
+    /// Case-insensitive regular expression matched against the title.
+    TitleRegex(String),
+    /// Executable name, with or without the `.exe` extension, case-insensitive.
+    ProcessName(String),
+    /// Window class (`GetClassNameW` / `WM_CLASS`), case-insensitive.
+    Class(String),
+    Pid(u32),
+    /// The topmost window that doesn't belong to Quack, i.e. the one the user
+    /// was in before switching to the overlay.
+    MostRecentlyFocused,
+}
+
+impl fmt::Display for WindowSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowSelector::Title(t) => write!(f, "title '{}'", t),
+            WindowSelector::TitleContains(t) => write!(f, "title containing '{}'", t),
+            WindowSelector::TitleRegex(r) => write!(f, "title matching /{}/", r),
+            WindowSelector::ProcessName(p) => write!(f, "process '{}'", p),
+            WindowSelector::Class(c) => write!(f, "class '{}'", c),
+            WindowSelector::Pid(pid) => write!(f, "pid {}", pid),
+            WindowSelector::MostRecentlyFocused => write!(f, "the most recently focused window"),
+        }
+    }
+}
+
+/// Lowercase executable name without `.exe`, so `Code.exe` and `code` compare
+/// equal. Used wherever a user-given process name is matched.
+pub fn process_stem(name: &str) -> String {
+    let name = name.to_lowercase();
+    name.strip_suffix(".exe").unwrap_or(&name).to_string()
+}
+
+/// A window considered by [`WindowSelector::resolve`].
+struct Candidate {
+    id: WindowId,
+    title: String,
+    pid: Option<u32>,
+    process_name: Option<String>,
+    tool_window: bool,
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.title)?;
+        match (&self.process_name, self.pid) {
+            (Some(name), Some(pid)) => write!(f, " ({}, pid {})", name, pid),
+            (None, Some(pid)) => write!(f, " (pid {})", pid),
+            (Some(name), None) => write!(f, " ({})", name),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl WindowSelector {
+    /// Finds the single window this selector refers to.
+    ///
+    /// Only titled, uncloaked windows of other processes are considered. If
+    /// several match, the error lists them so the caller can narrow it down;
+    /// exact titles pick the topmost match instead.
+    pub fn resolve(&self, platform: &impl Platform) -> Result<WindowId, QuackError> {
+        let own_pid = std::process::id();
+        let candidates = platform
+            .list_windows()
+            .into_iter()
+            .filter(|w| !w.cloaked)
+            .filter_map(|w| {
+                let pid = platform.window_pid(w.id);
+                if pid == Some(own_pid) {
+                    return None;
+                }
+                let title = platform.window_title(w.id);
+                if title.is_empty() {
+                    return None;
+                }
+                Some(Candidate {
+                    id: w.id,
+                    title,
+                    pid,
+                    process_name: platform
+                        .exe_path(w.id)
+                        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned())),
+                    tool_window: w.tool_window,
+                })
+            });
+
+        let matches: Vec<Candidate> = match self {
+            // Windows are listed in z-order, and activating a window raises it.
+            WindowSelector::MostRecentlyFocused => {
+                candidates.filter(|c| !c.tool_window).take(1).collect()
+            }
+            WindowSelector::Title(title) => {
+                candidates.filter(|c| c.title == *title).take(1).collect()
+            }
+            WindowSelector::TitleContains(needle) => {
+                let needle = needle.to_lowercase();
+                candidates
+                    .filter(|c| c.title.to_lowercase().contains(&needle))
+                    .collect()
+            }
+            WindowSelector::TitleRegex(pattern) => {
+                let re = RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| {
+                        QuackError::invalid_argument(format!("Invalid title regex: {}", e))
+                    })?;
+                candidates.filter(|c| re.is_match(&c.title)).collect()
+            }
+            WindowSelector::ProcessName(name) => {
+                let name = process_stem(name);
+                candidates
+                    .filter(|c| {
+                        c.process_name
+                            .as_ref()
+                            .is_some_and(|p| process_stem(p) == name)
+                    })
+                    .collect()
+            }
+            WindowSelector::Class(class) => candidates
+                .filter(|c| {
+                    platform
+                        .window_class(c.id)
+                        .is_some_and(|wc| wc.eq_ignore_ascii_case(class))
+                })
+                .collect(),
+            WindowSelector::Pid(pid) => candidates.filter(|c| c.pid == Some(*pid)).collect(),
+        };
+
+        match matches.as_slice() {
+            [] => Err(QuackError::WindowNotFound {
+                selector: self.to_string(),
+            }),
+            [only] => Ok(only.id),
+            many => Err(QuackError::AmbiguousWindow {
+                selector: self.to_string(),
+                candidates: many.iter().map(|c| c.to_string()).collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injection::{plan::InputEvent, PasteContent};
+    use crate::platform::{MonitorInfo, NativeWindow, ScreenCapture, ScreenTarget, WindowBounds};
+    use image::RgbaImage;
+    use std::path::PathBuf;
+
+    #[derive(Clone, Default)]
+    struct FakeWindow {
+        title: &'static str,
+        pid: u32,
+        exe: Option<&'static str>,
+        class: Option<&'static str>,
+        cloaked: bool,
+        tool_window: bool,
+    }
+
+    fn window(title: &'static str, pid: u32, exe: &'static str) -> FakeWindow {
+        FakeWindow {
+            title,
+            pid,
+            exe: Some(exe),
+            ..Default::default()
+        }
+    }
+
+    /// Windows in z-order, topmost first. The id of each is its position
+    /// plus one; only what `resolve` reads is implemented.
+    struct FakePlatform(Vec<FakeWindow>);
+
+    impl FakePlatform {
+        fn get(&self, window: WindowId) -> &FakeWindow {
+            &self.0[window.as_raw() as usize - 1]
+        }
+    }
+
+    fn id(raw: isize) -> WindowId {
+        WindowId::from_raw(raw).unwrap()
+    }
+
+    impl Platform for FakePlatform {
+        fn foreground_window(&self) -> Option<WindowId> {
+            unimplemented!()
+        }
+        fn list_windows(&self) -> Vec<NativeWindow> {
+            self.0
+                .iter()
+                .enumerate()
+                .map(|(i, w)| NativeWindow {
+                    id: id(i as isize + 1),
+                    bounds: WindowBounds::default(),
+                    cloaked: w.cloaked,
+                    tool_window: w.tool_window,
+                })
+                .collect()
+        }
+        fn window_title(&self, window: WindowId) -> String {
+            self.get(window).title.to_string()
+        }
+        fn window_pid(&self, window: WindowId) -> Option<u32> {
+            Some(self.get(window).pid)
+        }
+        fn window_class(&self, window: WindowId) -> Option<String> {
+            self.get(window).class.map(str::to_string)
+        }
+        fn exe_path(&self, window: WindowId) -> Option<PathBuf> {
+            self.get(window).exe.map(PathBuf::from)
+        }
+        fn window_icon_base64(&self, _window: WindowId) -> Option<String> {
+            unimplemented!()
+        }
+        fn watch_foreground(
+            &self,
+            _on_change: &mut dyn FnMut(Option<WindowId>, String),
+        ) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn focus_window(&self, _window: WindowId) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn send_input(&self, _events: &[InputEvent]) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn is_elevated(&self, _window: WindowId) -> bool {
+            unimplemented!()
+        }
+        fn on_secure_desktop(&self) -> bool {
+            unimplemented!()
+        }
+        fn inject_text(&self, _window: WindowId, _text: &str) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn paste_text(&self, _window: WindowId, _content: &PasteContent) -> Result<(), QuackError> {
+            unimplemented!()
+        }
+        fn monitors(&self) -> Result<Vec<MonitorInfo>, QuackError> {
+            unimplemented!()
+        }
+        fn capture_screen(&self, _target: ScreenTarget) -> Result<ScreenCapture, QuackError> {
+            unimplemented!()
+        }
+        fn capture_region(&self, _bounds: WindowBounds) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+        fn capture_window(&self, _window: WindowId) -> Result<RgbaImage, QuackError> {
+            unimplemented!()
+        }
+    }
+
+    /// A desktop with Quack's own overlay on top, followed by two editors.
+    fn desktop() -> FakePlatform {
+        FakePlatform(vec![
+            window("Quack", std::process::id(), "/opt/quack/quack"),
+            FakeWindow {
+                class: Some("Code"),
+                ..window("main.rs - Visual Studio Code", 100, "C:/Apps/Code.exe")
+            },
+            window("Untitled - Notepad", 200, "C:/Windows/notepad.exe"),
+            window("notes.txt - Notepad", 201, "C:/Windows/notepad.exe"),
+        ])
+    }
+
+    fn title(s: &str) -> WindowSelector {
+        WindowSelector::Title(s.to_string())
+    }
+
+    fn contains(s: &str) -> WindowSelector {
+        WindowSelector::TitleContains(s.to_string())
+    }
+
+    fn regex(s: &str) -> WindowSelector {
+        WindowSelector::TitleRegex(s.to_string())
+    }
+
+    #[test]
+    fn resolves_a_single_match() {
+        let platform = desktop();
+        let cases = [
+            (contains("visual studio"), 2),
+            (contains("UNTITLED"), 3),
+            (regex(r"^NOTES\.txt"), 4),
+            (regex("code$"), 2),
+            (WindowSelector::ProcessName("code".to_string()), 2),
+            (WindowSelector::Class("CODE".to_string()), 2),
+            (WindowSelector::Pid(201), 4),
+            (WindowSelector::MostRecentlyFocused, 2),
+        ];
+        for (selector, expected) in cases {
+            assert_eq!(
+                selector.resolve(&platform).ok(),
+                Some(id(expected)),
+                "{}",
+                selector
+            );
+        }
+    }
+
+    #[test]
+    fn exact_title_picks_the_topmost_match() {
+        let mut platform = desktop();
+        platform
+            .0
+            .push(window("Untitled - Notepad", 202, "notepad.exe"));
+        assert_eq!(
+            title("Untitled - Notepad").resolve(&platform).ok(),
+            Some(id(3))
+        );
+        // Exact means exact, case included.
+        assert!(matches!(
+            title("untitled - notepad").resolve(&platform),
+            Err(QuackError::WindowNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn lists_candidates_when_ambiguous() {
+        let platform = desktop();
+        for selector in [
+            contains("notepad"),
+            regex("notepad"),
+            WindowSelector::ProcessName("NOTEPAD.EXE".to_string()),
+        ] {
+            let Err(QuackError::AmbiguousWindow { candidates, .. }) = selector.resolve(&platform)
+            else {
+                panic!("expected {} to be ambiguous", selector);
+            };
+            assert_eq!(
+                candidates,
+                [
+                    "\"Untitled - Notepad\" (notepad.exe, pid 200)",
+                    "\"notes.txt - Notepad\" (notepad.exe, pid 201)",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_an_invalid_regex() {
+        assert!(matches!(
+            regex("(unclosed").resolve(&desktop()),
+            Err(QuackError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn skips_own_cloaked_and_untitled_windows() {
+        let platform = FakePlatform(vec![
+            window("Quack", std::process::id(), "/opt/quack/quack"),
+            FakeWindow {
+                cloaked: true,
+                ..window("Other desktop - Notepad", 300, "notepad.exe")
+            },
+            window("", 301, "notepad.exe"),
+        ]);
+        for selector in [
+            contains("quack"),
+            contains("notepad"),
+            WindowSelector::ProcessName("notepad".to_string()),
+            WindowSelector::Pid(301),
+            WindowSelector::MostRecentlyFocused,
+        ] {
+            assert!(
+                matches!(
+                    selector.resolve(&platform),
+                    Err(QuackError::WindowNotFound { .. })
+                ),
+                "{}",
+                selector
+            );
+        }
+    }
+
+    #[test]
+    fn most_recently_focused_skips_tool_windows() {
+        let mut platform = desktop();
+        platform.0.insert(
+            1,
+            FakeWindow {
+                tool_window: true,
+                ..window("Color picker", 400, "picker.exe")
+            },
+        );
+        assert_eq!(
+            WindowSelector::MostRecentlyFocused.resolve(&platform).ok(),
+            Some(id(3))
+        );
+    }
+}
@@ -33,6 +33,7 @@ import Notes from "./routes/app/notes/page";
 import Agents from "./routes/app/agents/page";
 import { emit, listen } from "@tauri-apps/api/event";
 import Agent from "./routes/app/agents/agent/page";
+import RegionSelector from "./routes/region-selector/page";
 
 function App() {
   const { darkTheme, initializeTheme } = useDarkThemeStore();
@@ -190,6 +191,7 @@ function App() {
     <Routes>
       <Route path="/" element={<Onboarding />} />
       <Route path="/overlay" element={<Overlay />} />
+      <Route path="/region-selector" element={<RegionSelector />} />
       <Route path="/app" element={<MainApp />}>
         <Route path="landing" element={<Landing />} />
         <Route path="chat" element={<ChatWindow />} />
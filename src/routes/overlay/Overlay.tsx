@@ -7,7 +7,7 @@ import { listen } from "@tauri-apps/api/event";
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
 import { useEffect, useRef, useState } from "react";
 import Overlay from "./components/OverlayCard";
-import { resize } from "@/utils/windowUtils";
+import { getAppIcon, resize } from "@/utils/windowUtils";
 import { motion } from "motion/react";
 
 
@@ -56,16 +56,16 @@ const MagicDot = () => {
 
     interface ActiveWindowChangedPayload {
       name?: string;
-      icon?: string; // data UR (e.g., data:image/png;base64,...)
+      icon_key?: string | null; // resolve with get_app_icon
     }
 
     const unlistenPromise = listen<ActiveWindowChangedPayload>(
       "active_window_changed",
       (event) => {
         if (event?.payload) {
-          const { name, icon } = event.payload;
+          const { name, icon_key } = event.payload;
           setWindowName(name ?? "");
-          setWindowIcon(icon ?? "");
+          getAppIcon(icon_key).then(setWindowIcon);
         }
       }
     );
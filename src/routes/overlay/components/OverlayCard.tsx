@@ -5,6 +5,7 @@ import {
   pinMagicDot,
   resize,
   refreshStyles,
+  getAppIcon,
 } from "@/utils/windowUtils";
 import { AnimatePresence, motion } from "framer-motion";
 import { OverlayButton } from "./OverlayComponents";
@@ -82,7 +83,7 @@ const Overlay = () => {
     invoke("start_window_watch").catch(() => {});
     const unlistenPromise = listen<{
       name?: string;
-      icon?: string;
+      icon_key?: string | null;
       hwnd?: number;
     }>("active_window_changed", (event) => {
       if (
@@ -90,7 +91,7 @@ const Overlay = () => {
         !event.payload.name.toLowerCase().includes("tauri")
       ) {
         setWindowName(event.payload.name);
-        setWindowIcon(event.payload.icon ?? "");
+        getAppIcon(event.payload.icon_key).then(setWindowIcon);
         if (typeof event.payload.hwnd === "number") {
           setWindowHwnd(event.payload.hwnd);
         }
@@ -483,9 +484,10 @@ const Overlay = () => {
     try {
       cancelScreenshotHide();
       if (windowHwnd == null) return;
-      const screenshot = (await invoke("capture_window_screenshot_by_hwnd", {
-        hwnd: windowHwnd,
-      })) as string;
+      const { url: screenshot } = await invoke<{ url: string }>(
+        "capture_window_screenshot_by_hwnd",
+        { hwnd: windowHwnd },
+      );
       console.log("Screenshot received, length:", screenshot.length);
       setWindowScreenshot(screenshot);
       setShowScreenshot(true);
@@ -255,10 +255,21 @@ export const ChatView = ({
     new Date().toLocaleTimeString([], { hour: "2-digit", minute: "2-digit" });
 
   const handleInject = async () => {
-    await invoke("inject_text_to_window_by_title", {
-      text: currResponse,
-      windowTitle: windowName,
-    });
+    const inject = (confirmation?: number) =>
+      invoke("inject_text_to_window_by_title", {
+        text: currResponse,
+        windowTitle: windowName,
+        options: { confirmation },
+      });
+    try {
+      await inject();
+    } catch (e: any) {
+      // Risky targets (terminals, admin consoles) need an explicit OK.
+      if (e?.code !== "confirmation_required") throw e;
+      if (window.confirm(`Insert the answer into ${e.details.target}?`)) {
+        await inject(e.details.token);
+      }
+    }
   };
 
   return (
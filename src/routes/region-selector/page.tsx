@@ -0,0 +1,115 @@
+import { useEffect, useRef, useState, type MouseEvent } from "react";
+import { useSearchParams } from "react-router-dom";
+import { invoke } from "@tauri-apps/api/core";
+
+interface Rect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+// Drags smaller than this (in CSS pixels) are treated as a stray click.
+const MIN_SELECTION_SIZE = 4;
+
+const toRect = (
+  start: { x: number; y: number },
+  end: { x: number; y: number },
+): Rect => ({
+  x: Math.min(start.x, end.x),
+  y: Math.min(start.y, end.y),
+  width: Math.abs(end.x - start.x),
+  height: Math.abs(end.y - start.y),
+});
+
+/*
+  Window covering one monitor while `start_region_selection` is running. It
+  shows the frame captured when the selection started, so the screen looks
+  frozen. The user drags a rectangle; Escape or a right click cancels.
+*/
+const RegionSelector = () => {
+  const [searchParams] = useSearchParams();
+  const monitor = Number(searchParams.get("monitor") ?? 0);
+  const frame = searchParams.get("frame");
+  const startRef = useRef<{ x: number; y: number } | null>(null);
+  const [rect, setRect] = useState<Rect | null>(null);
+
+  const finish = (selection: Rect | null) => {
+    invoke("finish_region_selection", { monitor, selection }).catch(
+      console.error,
+    );
+  };
+
+  useEffect(() => {
+    const onKeyDown = (e: KeyboardEvent) => {
+      if (e.key === "Escape") finish(null);
+    };
+    window.addEventListener("keydown", onKeyDown);
+    return () => window.removeEventListener("keydown", onKeyDown);
+  }, []);
+
+  const onMouseDown = (e: MouseEvent) => {
+    if (e.button !== 0) {
+      finish(null);
+      return;
+    }
+    startRef.current = { x: e.clientX, y: e.clientY };
+    setRect({ x: e.clientX, y: e.clientY, width: 0, height: 0 });
+  };
+
+  const onMouseMove = (e: MouseEvent) => {
+    if (!startRef.current) return;
+    setRect(toRect(startRef.current, { x: e.clientX, y: e.clientY }));
+  };
+
+  const onMouseUp = (e: MouseEvent) => {
+    if (!startRef.current) return;
+    const selection = toRect(startRef.current, { x: e.clientX, y: e.clientY });
+    startRef.current = null;
+    if (
+      selection.width < MIN_SELECTION_SIZE ||
+      selection.height < MIN_SELECTION_SIZE
+    ) {
+      setRect(null);
+      return;
+    }
+    finish(selection);
+  };
+
+  return (
+    <div
+      className="fixed inset-0 cursor-crosshair select-none"
+      style={{
+        // The frame is in physical pixels; stretch it over the CSS pixels.
+        backgroundImage: frame ? `url("${frame}")` : undefined,
+        backgroundSize: "100% 100%",
+      }}
+      onMouseDown={onMouseDown}
+      onMouseMove={onMouseMove}
+      onMouseUp={onMouseUp}
+      onContextMenu={(e) => e.preventDefault()}
+    >
+      {!rect && (
+        <div
+          className="absolute inset-0"
+          style={{ background: "rgba(0, 0, 0, 0.25)" }}
+        />
+      )}
+      {rect && (
+        <div
+          className="absolute border-2 border-white"
+          style={{
+            left: rect.x,
+            top: rect.y,
+            width: rect.width,
+            height: rect.height,
+            // Dim everything outside the selection.
+            boxShadow: "0 0 0 9999px rgba(0, 0, 0, 0.25)",
+          }}
+        />
+      )}
+    </div>
+  );
+};
+
+export default RegionSelector;
@@ -56,3 +56,21 @@ export const pinMagicDot = async () => {
     console.error("Failed to pin magic dot:", err);
   }
 };
+
+const appIconCache = new Map<string, string>();
+
+// Resolves an `icon_key` from `active_window_changed` to a data URL, fetching
+// each icon from the backend only once.
+export const getAppIcon = async (key?: string | null): Promise<string> => {
+  if (!key) return "";
+  const cached = appIconCache.get(key);
+  if (cached !== undefined) return cached;
+  try {
+    const icon = (await invoke<string | null>("get_app_icon", { key })) ?? "";
+    appIconCache.set(key, icon);
+    return icon;
+  } catch (err) {
+    console.error("Failed to fetch app icon:", err);
+    return "";
+  }
+};